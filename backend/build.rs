@@ -0,0 +1,25 @@
+use std::{env, path::Path, process::Command};
+
+// Embeds the commit the binary was built from as `GIT_SHA`, reported by `/version`.
+// Builds outside a git checkout (e.g. nix) can pass it in the environment.
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    for path in ["../.git/HEAD", "../.git/refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={path}");
+        }
+    }
+    let sha = env::var("GIT_SHA").ok().or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|sha| sha.trim().to_string())
+    });
+    println!(
+        "cargo:rustc-env=GIT_SHA={}",
+        sha.unwrap_or_else(|| "unknown".to_string())
+    );
+}
@@ -0,0 +1,29 @@
+-- Categories Table
+CREATE TABLE IF NOT EXISTS categories (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    parent_id BIGINT,
+    FOREIGN KEY (parent_id) REFERENCES categories(id)
+);
+
+-- Products Table
+CREATE TABLE IF NOT EXISTS products (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    price BIGINT NOT NULL,
+    category_id BIGINT,
+    FOREIGN KEY (category_id) REFERENCES categories(id)
+);
+
+CREATE TABLE IF NOT EXISTS admins (
+    id BIGSERIAL PRIMARY KEY,
+    password VARCHAR(255) NOT NULL,
+    username VARCHAR(255) NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tokens (
+    token VARCHAR(64) PRIMARY KEY,
+    admin_id BIGINT,
+    FOREIGN KEY (admin_id) REFERENCES admins(id)
+);
@@ -0,0 +1,15 @@
+-- Synonym groups: every term in a group matches the others in product search
+CREATE TABLE IF NOT EXISTS synonym_groups (
+    id BIGSERIAL PRIMARY KEY
+);
+
+CREATE TABLE IF NOT EXISTS synonyms (
+    term VARCHAR(255) NOT NULL PRIMARY KEY,
+    group_id BIGINT NOT NULL,
+    FOREIGN KEY (group_id) REFERENCES synonym_groups(id) ON DELETE CASCADE
+);
+
+-- Stop words are dropped from search queries before matching
+CREATE TABLE IF NOT EXISTS stop_words (
+    word VARCHAR(255) NOT NULL PRIMARY KEY
+);
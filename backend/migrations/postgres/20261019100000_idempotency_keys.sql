@@ -0,0 +1,13 @@
+-- Idempotency-Key of the mutating admin routes, with the request it was first
+-- sent with and the response replayed to its retries. The response columns
+-- are NULL while the first request runs.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    key VARCHAR(255) NOT NULL PRIMARY KEY,
+    fingerprint VARCHAR(64) NOT NULL,
+    created_at BIGINT NOT NULL,
+    status INTEGER,
+    content_type VARCHAR(255),
+    body BYTEA
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,16 @@
+-- Idempotency keys are scoped by the client that sent them, so two admins
+-- picking the same key don't see each other's responses. Kept responses only
+-- serve retries, they are dropped rather than migrated.
+DROP TABLE IF EXISTS idempotency_keys;
+CREATE TABLE idempotency_keys (
+    client VARCHAR(64) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    fingerprint VARCHAR(64) NOT NULL,
+    created_at INTEGER NOT NULL,
+    status INTEGER,
+    content_type VARCHAR(255),
+    body BLOB,
+    PRIMARY KEY (client, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,253 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::search::tokenize;
+
+pub mod memory;
+pub mod noop;
+pub mod redis;
+pub mod single_flight;
+
+// Storage for cached responses. Values are serialized responses, `tags` register
+// the entities a response was built from so writes can invalidate it.
+#[async_trait]
+pub trait Cache: Send + Sync {
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>>;
+    // Values of `keys` in their order, in one round trip to the backing store
+    async fn get_many(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>>;
+    async fn set(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+        tags: &[CacheTag],
+    ) -> anyhow::Result<()>;
+    // Every cached key of `route`
+    async fn keys(&self, route: &'static str) -> anyhow::Result<Vec<String>>;
+    async fn delete(&self, keys: &[String]) -> anyhow::Result<()>;
+    // Deletes every entry registered under any of `tags`, returns how many were deleted
+    async fn invalidate(&self, tags: &[CacheTag]) -> anyhow::Result<usize>;
+    // Deletes every entry, returns how many were deleted
+    async fn purge(&self) -> anyhow::Result<usize>;
+    // Fails when the backing store is unreachable
+    async fn ping(&self) -> anyhow::Result<()>;
+    // Releases connections to the backing store, called once on shutdown
+    async fn close(&self);
+}
+
+// Builds the cache key of a response from its route and every request parameter.
+// Parameters are ordered by name and text is normalised, so equivalent requests
+// share one key and different pages, sizes or filters never collide:
+// `/product/search?page=1&page_size=50&query=red%20tee`
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    route: &'static str,
+    params: BTreeMap<&'static str, String>,
+}
+impl CacheKey {
+    pub fn new(route: &'static str) -> Self {
+        Self {
+            route,
+            params: BTreeMap::new(),
+        }
+    }
+    pub fn param(mut self, name: &'static str, value: impl ToString) -> Self {
+        self.params.insert(name, value.to_string());
+        self
+    }
+    // Free text is compared the way search compares it: case and whitespace are ignored
+    pub fn text(self, name: &'static str, value: &str) -> Self {
+        self.param(name, tokenize(value).join(" "))
+    }
+    pub fn build(&self) -> String {
+        if self.params.is_empty() {
+            return self.route.to_string();
+        }
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, encode(value)))
+            .collect();
+        format!("{}?{}", self.route, params.join("&"))
+    }
+    // true when `key` was built for `route`
+    pub fn is_route(key: &str, route: &str) -> bool {
+        key.strip_prefix(route)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('?'))
+    }
+    // Reads a parameter back out of a key built by `build`
+    pub fn param_of(key: &str, name: &str) -> Option<String> {
+        let (_, params) = key.split_once('?')?;
+        params
+            .split('&')
+            .filter_map(|param| param.split_once('='))
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| decode(value))
+    }
+}
+
+// Percent-encodes separators, `%` and Redis glob characters so values can't
+// break the key layout or match patterns
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '%' | '&' | '=' | '?' | '*' | '[' | ']' | '\\') || c.is_whitespace() {
+            let mut buf = [0u8; 4];
+            for byte in c.encode_utf8(&mut buf).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = value
+            .get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Entities a cached response was built from. Every cached key is registered under
+// its tags so admin writes can purge exactly the responses that mention an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheTag {
+    Product(i64),
+    Category(i64),
+    // any search result, product names they match against may have changed
+    Search,
+    // any page of `/products`, pages shift when products are added or removed
+    ProductPages,
+    // the list of top-level categories
+    CategoryTree,
+}
+impl CacheTag {
+    pub const PREFIX: &'static str = "tag:";
+    pub fn key(&self) -> String {
+        match self {
+            CacheTag::Product(id) => format!("{}product:{}", Self::PREFIX, id),
+            CacheTag::Category(id) => format!("{}category:{}", Self::PREFIX, id),
+            CacheTag::Search => format!("{}search", Self::PREFIX),
+            CacheTag::ProductPages => format!("{}product_pages", Self::PREFIX),
+            CacheTag::CategoryTree => format!("{}category_tree", Self::PREFIX),
+        }
+    }
+}
+
+// A cached response with the time it was computed. Entries are kept past their
+// TTL for the stale window, during which they are served while a refresh runs.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CacheEntry<T> {
+    pub value: T,
+    // unix seconds
+    pub stored_at: u64,
+    pub fresh_until: u64,
+}
+impl<T> CacheEntry<T> {
+    pub fn new(value: T, ttl: Duration) -> Self {
+        let stored_at = unix_now();
+        Self {
+            value,
+            stored_at,
+            fresh_until: stored_at + ttl.as_secs(),
+        }
+    }
+    pub fn is_fresh(&self) -> bool {
+        unix_now() < self.fresh_until
+    }
+}
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn params_are_ordered_by_name() {
+        let key = CacheKey::new("/products")
+            .param("page_size", 50)
+            .param("page", 1)
+            .build();
+        assert_eq!(key, "/products?page=1&page_size=50");
+        assert_eq!(CacheKey::new("/categories").build(), "/categories");
+    }
+
+    #[test]
+    fn text_ignores_case_and_whitespace() {
+        let key = |query| {
+            CacheKey::new("/product/search")
+                .text("query", query)
+                .build()
+        };
+        assert_eq!(key("  Red   TEE "), key("red tee"));
+        assert_eq!(key("red tee"), "/product/search?query=red%20tee");
+    }
+
+    // A value can't pass for other parameters or widen a Redis pattern
+    #[test]
+    fn values_are_encoded() {
+        let injected = CacheKey::new("/product/search")
+            .param("query", "tee&page=2")
+            .param("page", 1)
+            .build();
+        let honest = CacheKey::new("/product/search")
+            .param("query", "tee")
+            .param("page", 2)
+            .build();
+        assert_ne!(injected, honest);
+        assert_eq!(injected, "/product/search?page=1&query=tee%26page%3D2");
+        let glob = CacheKey::new("/product/search")
+            .param("query", "*[a]?\\%")
+            .build();
+        assert_eq!(glob, "/product/search?query=%2A%5Ba%5D%3F%5C%25");
+    }
+
+    #[test]
+    fn params_read_back_decoded() {
+        for value in ["tee&page=2", "100% *cotton*", "café crème", "%zz", ""] {
+            let key = CacheKey::new("/product/search")
+                .param("query", value)
+                .param("page", 3)
+                .build();
+            assert_eq!(CacheKey::param_of(&key, "query").as_deref(), Some(value));
+            assert_eq!(CacheKey::param_of(&key, "page").as_deref(), Some("3"));
+            assert_eq!(CacheKey::param_of(&key, "missing"), None);
+        }
+        assert_eq!(CacheKey::param_of("/categories", "page"), None);
+    }
+
+    #[test]
+    fn routes_match_whole_paths() {
+        assert!(CacheKey::is_route("/products?page=1", "/products"));
+        assert!(CacheKey::is_route("/products", "/products"));
+        assert!(!CacheKey::is_route("/products_v2?page=1", "/products"));
+        assert!(!CacheKey::is_route("/product?id=1", "/products"));
+    }
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use lru::LruCache;
+use tracing::instrument;
+
+use crate::cache::{Cache, CacheKey, CacheTag};
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+    tags: Vec<CacheTag>,
+}
+struct Inner {
+    entries: LruCache<String, Entry>,
+    tags: HashMap<CacheTag, HashSet<String>>,
+}
+impl Inner {
+    fn untag(&mut self, key: &str, entry: &Entry) {
+        for tag in &entry.tags {
+            if let Some(keys) = self.tags.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(tag);
+                }
+            }
+        }
+    }
+    fn remove(&mut self, key: &str) -> bool {
+        match self.entries.pop(key) {
+            Some(entry) => {
+                self.untag(key, &entry);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// In-process LRU cache with per-entry TTL, used when no Redis is deployed.
+// Entries are local to this instance, so it only suits single-instance deployments.
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+}
+impl MemoryCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::new(capacity),
+                tags: HashMap::new(),
+            }),
+        }
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // the map is left consistent between statements, a poisoned lock is still usable
+        self.inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+#[async_trait]
+impl Cache for MemoryCache {
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let mut inner = self.lock();
+        let expired = match inner.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => {
+                return Ok(Some(entry.value.clone()))
+            }
+            Some(_) => true,
+            None => false,
+        };
+        if expired {
+            inner.remove(key);
+        }
+        Ok(None)
+    }
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn get_many(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+    #[instrument(skip(self, value), fields(cache.backend = "memory"), err)]
+    async fn set(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+        tags: &[CacheTag],
+    ) -> anyhow::Result<()> {
+        let mut inner = self.lock();
+        inner.remove(key);
+        for tag in tags {
+            inner.tags.entry(*tag).or_default().insert(key.to_string());
+        }
+        let entry = Entry {
+            value,
+            expires_at: Instant::now() + ttl,
+            tags: tags.to_vec(),
+        };
+        if let Some((evicted_key, evicted)) = inner.entries.push(key.to_string(), entry) {
+            inner.untag(&evicted_key, &evicted);
+        }
+        Ok(())
+    }
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn keys(&self, route: &'static str) -> anyhow::Result<Vec<String>> {
+        let inner = self.lock();
+        Ok(inner
+            .entries
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| CacheKey::is_route(key, route))
+            .cloned()
+            .collect())
+    }
+    #[instrument(skip(self, keys), fields(cache.backend = "memory", keys = keys.len()), err)]
+    async fn delete(&self, keys: &[String]) -> anyhow::Result<()> {
+        let mut inner = self.lock();
+        for key in keys {
+            inner.remove(key);
+        }
+        Ok(())
+    }
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn invalidate(&self, tags: &[CacheTag]) -> anyhow::Result<usize> {
+        let mut inner = self.lock();
+        let mut invalidated = 0;
+        for tag in tags {
+            let keys = inner.tags.remove(tag).unwrap_or_default();
+            for key in keys {
+                if inner.remove(&key) {
+                    invalidated += 1;
+                }
+            }
+        }
+        Ok(invalidated)
+    }
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn purge(&self) -> anyhow::Result<usize> {
+        let mut inner = self.lock();
+        let purged = inner.entries.len();
+        inner.entries.clear();
+        inner.tags.clear();
+        Ok(purged)
+    }
+    #[instrument(skip(self), fields(cache.backend = "memory"), err)]
+    async fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn close(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn cache(capacity: usize) -> MemoryCache {
+        MemoryCache::new(NonZeroUsize::new(capacity).unwrap())
+    }
+
+    async fn set(cache: &MemoryCache, key: &str, tags: &[CacheTag]) {
+        cache.set(key, key.to_string(), TTL, tags).await.unwrap();
+    }
+
+    async fn cached(cache: &MemoryCache, key: &str) -> bool {
+        cache.get(key).await.unwrap().is_some()
+    }
+
+    #[tokio::test]
+    async fn invalidate_deletes_the_tagged_entries() {
+        let cache = cache(10);
+        set(
+            &cache,
+            "/category?id=1",
+            &[CacheTag::Category(1), CacheTag::Product(7)],
+        )
+        .await;
+        set(&cache, "/category?id=2", &[CacheTag::Category(2)]).await;
+        set(
+            &cache,
+            "/products?page=0",
+            &[CacheTag::ProductPages, CacheTag::Product(7)],
+        )
+        .await;
+
+        assert_eq!(cache.invalidate(&[CacheTag::Product(7)]).await.unwrap(), 2);
+        assert!(!cached(&cache, "/category?id=1").await);
+        assert!(!cached(&cache, "/products?page=0").await);
+        assert!(cached(&cache, "/category?id=2").await);
+        // the deleted entries left no tags behind
+        assert_eq!(cache.invalidate(&[CacheTag::Category(1)]).await.unwrap(), 0);
+        assert_eq!(
+            cache.invalidate(&[CacheTag::ProductPages]).await.unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn entries_under_several_tags_are_counted_once() {
+        let cache = cache(10);
+        set(
+            &cache,
+            "/category?id=1",
+            &[CacheTag::Category(1), CacheTag::Product(7)],
+        )
+        .await;
+        let tags = [
+            CacheTag::Category(1),
+            CacheTag::Product(7),
+            CacheTag::Search,
+        ];
+        assert_eq!(cache.invalidate(&tags).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn replacing_an_entry_replaces_its_tags() {
+        let cache = cache(10);
+        set(&cache, "/product?id=1", &[CacheTag::Category(1)]).await;
+        set(&cache, "/product?id=1", &[CacheTag::Category(2)]).await;
+        assert_eq!(cache.invalidate(&[CacheTag::Category(1)]).await.unwrap(), 0);
+        assert!(cached(&cache, "/product?id=1").await);
+        assert_eq!(cache.invalidate(&[CacheTag::Category(2)]).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn evicted_entries_are_untagged() {
+        let cache = cache(1);
+        set(&cache, "/product?id=1", &[CacheTag::Product(1)]).await;
+        set(&cache, "/product?id=2", &[CacheTag::Product(2)]).await;
+        assert!(!cached(&cache, "/product?id=1").await);
+        assert!(!cache.lock().tags.contains_key(&CacheTag::Product(1)));
+        assert_eq!(cache.invalidate(&[CacheTag::Product(2)]).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_not_served() {
+        let cache = cache(10);
+        cache
+            .set("/product?id=1", "stale".to_string(), Duration::ZERO, &[])
+            .await
+            .unwrap();
+        assert!(!cached(&cache, "/product?id=1").await);
+    }
+
+    #[tokio::test]
+    async fn purge_deletes_everything() {
+        let cache = cache(10);
+        set(&cache, "/products?page=0", &[CacheTag::ProductPages]).await;
+        set(&cache, "/products?page=1", &[CacheTag::ProductPages]).await;
+        set(&cache, "/categories", &[]).await;
+        assert_eq!(cache.keys("/products").await.unwrap().len(), 2);
+        assert_eq!(cache.purge().await.unwrap(), 3);
+        assert!(cache.keys("/products").await.unwrap().is_empty());
+        assert_eq!(
+            cache.invalidate(&[CacheTag::ProductPages]).await.unwrap(),
+            0
+        );
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::cache::{Cache, CacheTag};
+
+// Caches nothing, every request reads the database
+pub struct NoopCache;
+#[async_trait]
+impl Cache for NoopCache {
+    async fn get(&self, _key: &str) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+    async fn get_many(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        Ok(vec![None; keys.len()])
+    }
+    async fn set(
+        &self,
+        _key: &str,
+        _value: String,
+        _ttl: Duration,
+        _tags: &[CacheTag],
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn keys(&self, _route: &'static str) -> anyhow::Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+    async fn delete(&self, _keys: &[String]) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn invalidate(&self, _tags: &[CacheTag]) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+    async fn purge(&self) -> anyhow::Result<usize> {
+        Ok(0)
+    }
+    async fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+    async fn close(&self) {}
+}
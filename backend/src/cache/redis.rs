@@ -0,0 +1,267 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use tracing::instrument;
+
+use crate::cache::{Cache, CacheKey, CacheTag};
+
+// `prefix` as a key namespace: empty or ending in `:`
+pub fn key_prefix(prefix: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with(':') {
+        prefix.to_string()
+    } else {
+        format!("{prefix}:")
+    }
+}
+
+pub struct RedisCache {
+    pool: deadpool_redis::Pool,
+    // namespace of every key this cache touches, empty or ending in `:`
+    prefix: String,
+}
+impl RedisCache {
+    pub fn new(pool: deadpool_redis::Pool, prefix: &str) -> Self {
+        Self {
+            pool,
+            prefix: key_prefix(prefix),
+        }
+    }
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+    fn tag_key(&self, tag: &CacheTag) -> String {
+        self.key(&tag.key())
+    }
+    // Stores `key` and adds it to the set of each tag. A tag set's expiry is only
+    // ever extended, so it outlives its longest lived member whatever order
+    // members with different TTLs are written in: NX gives a new set the member's
+    // TTL, GT lengthens it for a longer lived one. Needs Redis 7.
+    fn set_pipeline(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+        tags: &[CacheTag],
+    ) -> redis::Pipeline {
+        let key = self.key(key);
+        let ttl = ttl.as_secs().max(1);
+        let mut pipe = redis::pipe();
+        pipe.set_ex(&key, value, ttl).ignore();
+        for tag in tags {
+            let tag_key = self.tag_key(tag);
+            pipe.sadd(&tag_key, &key).ignore();
+            for option in ["NX", "GT"] {
+                pipe.cmd("EXPIRE")
+                    .arg(&tag_key)
+                    .arg(ttl)
+                    .arg(option)
+                    .ignore();
+            }
+        }
+        pipe
+    }
+    async fn scan(
+        &self,
+        conn: &mut deadpool_redis::Connection,
+        pattern: String,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut iter = conn.scan_match::<_, String>(pattern).await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+#[async_trait]
+impl Cache for RedisCache {
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn get(&self, key: &str) -> anyhow::Result<Option<String>> {
+        let mut conn = self.pool.get().await?;
+        Ok(conn.get(self.key(key)).await?)
+    }
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn get_many(&self, keys: &[String]) -> anyhow::Result<Vec<Option<String>>> {
+        // MGET needs at least one key
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.pool.get().await?;
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+        Ok(conn.mget(keys).await?)
+    }
+    #[instrument(skip(self, value), fields(cache.backend = "redis"), err)]
+    async fn set(
+        &self,
+        key: &str,
+        value: String,
+        ttl: Duration,
+        tags: &[CacheTag],
+    ) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+        self.set_pipeline(key, value, ttl, tags)
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn keys(&self, route: &'static str) -> anyhow::Result<Vec<String>> {
+        let mut conn = self.pool.get().await?;
+        let keys = self
+            .scan(&mut conn, format!("{}{}*", self.prefix, route))
+            .await?;
+        Ok(keys
+            .into_iter()
+            .filter_map(|key| key.strip_prefix(&self.prefix).map(str::to_string))
+            .filter(|key| CacheKey::is_route(key, route))
+            .collect())
+    }
+    #[instrument(skip(self, keys), fields(cache.backend = "redis", keys = keys.len()), err)]
+    async fn delete(&self, keys: &[String]) -> anyhow::Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        let keys: Vec<String> = keys.iter().map(|key| self.key(key)).collect();
+        for chunk in keys.chunks(500) {
+            conn.del::<_, ()>(chunk).await?;
+        }
+        Ok(())
+    }
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn invalidate(&self, tags: &[CacheTag]) -> anyhow::Result<usize> {
+        if tags.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.pool.get().await?;
+        let mut pipe = redis::pipe();
+        for tag in tags {
+            pipe.smembers(self.tag_key(tag));
+        }
+        // members are stored with the prefix already applied
+        let members: Vec<Vec<String>> = pipe.query_async(&mut conn).await?;
+        let mut keys: Vec<String> = members.into_iter().flatten().collect();
+        let invalidated = keys.len();
+        keys.extend(tags.iter().map(|tag| self.tag_key(tag)));
+        conn.del::<_, ()>(keys).await?;
+        Ok(invalidated)
+    }
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn purge(&self) -> anyhow::Result<usize> {
+        let mut conn = self.pool.get().await?;
+        let mut keys = self.scan(&mut conn, format!("{}/*", self.prefix)).await?;
+        keys.extend(
+            self.scan(&mut conn, format!("{}{}*", self.prefix, CacheTag::PREFIX))
+                .await?,
+        );
+        for chunk in keys.chunks(500) {
+            conn.del::<_, ()>(chunk).await?;
+        }
+        Ok(keys.len())
+    }
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn ping(&self) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+        redis::cmd("PING").query_async::<()>(&mut conn).await?;
+        Ok(())
+    }
+    async fn close(&self) {
+        // idle connections are dropped now, checked out ones when they are returned
+        self.pool.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(url: &str) -> RedisCache {
+        let config = deadpool_redis::Config::from_url(url);
+        let pool = config
+            .create_pool(Some(deadpool_redis::Runtime::Tokio1))
+            .unwrap();
+        RedisCache::new(pool, "test")
+    }
+
+    // The commands of a pipeline, as strings
+    fn commands(pipe: &redis::Pipeline) -> Vec<Vec<String>> {
+        pipe.cmd_iter()
+            .map(|cmd| {
+                cmd.args_iter()
+                    .map(|arg| match arg {
+                        redis::Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                        redis::Arg::Cursor => "<cursor>".to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tag_sets_expiry_is_only_extended() {
+        // building a pipeline doesn't connect
+        let cache = cache("redis://127.0.0.1:1");
+        let pipe = cache.set_pipeline(
+            "/product?id=1",
+            "{}".to_string(),
+            Duration::from_secs(30),
+            &[CacheTag::Product(1)],
+        );
+        let expected: [&[&str]; 4] = [
+            &["SETEX", "test:/product?id=1", "30", "{}"],
+            &["SADD", "test:tag:product:1", "test:/product?id=1"],
+            &["EXPIRE", "test:tag:product:1", "30", "NX"],
+            &["EXPIRE", "test:tag:product:1", "30", "GT"],
+        ];
+        assert_eq!(commands(&pipe), expected);
+    }
+
+    // A short lived entry tagged after a long lived one doesn't shorten the tag set,
+    // so invalidating the tag still finds the long lived entry
+    #[tokio::test]
+    #[ignore = "needs a Redis 7 server at REDIS_URL"]
+    async fn short_ttls_dont_shorten_tag_sets() {
+        let url = std::env::var("REDIS_URL").unwrap_or("redis://127.0.0.1:6379".to_string());
+        let cache = cache(&url);
+        let tag = [CacheTag::Category(1)];
+        cache.purge().await.unwrap();
+        cache
+            .set(
+                "/category?id=1",
+                "{}".to_string(),
+                Duration::from_secs(300),
+                &tag,
+            )
+            .await
+            .unwrap();
+        cache
+            .set(
+                "/product/search?query=a",
+                "[]".to_string(),
+                Duration::from_secs(1),
+                &tag,
+            )
+            .await
+            .unwrap();
+        let mut conn = cache.pool.get().await.unwrap();
+        let tag_ttl: i64 = conn.ttl(cache.tag_key(&tag[0])).await.unwrap();
+        assert!(tag_ttl > 1, "tag set expires in {tag_ttl}s");
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        assert_eq!(cache.invalidate(&tag).await.unwrap(), 1);
+        assert!(cache.get("/category?id=1").await.unwrap().is_none());
+        // a new set takes its first member's TTL
+        cache
+            .set(
+                "/category?id=2",
+                "{}".to_string(),
+                Duration::from_secs(60),
+                &tag,
+            )
+            .await
+            .unwrap();
+        let tag_ttl: i64 = conn.ttl(cache.tag_key(&tag[0])).await.unwrap();
+        assert!((1..=60).contains(&tag_ttl), "tag set expires in {tag_ttl}s");
+        cache.purge().await.unwrap();
+    }
+}
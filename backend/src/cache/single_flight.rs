@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use axum::http::StatusCode;
+use tokio::sync::broadcast;
+use tokio_util::task::TaskTracker;
+use tracing::{info_span, Instrument};
+
+type FlightResult = Result<String, (StatusCode, String)>;
+type Flights = Arc<Mutex<HashMap<String, broadcast::Sender<FlightResult>>>>;
+
+// Coalesces concurrent computations of the same cache key: the first caller starts
+// the computation on a background task and everyone asking for the key meanwhile
+// waits for that one result. Running it on its own task means a cancelled request
+// doesn't abort the computation the other waiters depend on.
+pub struct SingleFlight {
+    flights: Flights,
+    // computations are awaited on shutdown so their results still get cached
+    tasks: TaskTracker,
+}
+impl SingleFlight {
+    pub fn new(tasks: TaskTracker) -> Self {
+        Self {
+            flights: Flights::default(),
+            tasks,
+        }
+    }
+    // Starts computing `key` unless that is already in progress, then waits for the result
+    pub async fn run<F>(&self, key: &str, compute: F) -> FlightResult
+    where
+        F: Future<Output = FlightResult> + Send + 'static,
+    {
+        let mut result = self.start(key, compute);
+        result.recv().await.unwrap_or_else(|_| {
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("computing {key} failed"),
+            ))
+        })
+    }
+    // Starts computing `key` unless that is already in progress, without waiting
+    pub fn start<F>(&self, key: &str, compute: F) -> broadcast::Receiver<FlightResult>
+    where
+        F: Future<Output = FlightResult> + Send + 'static,
+    {
+        let mut flights = lock(&self.flights);
+        if let Some(flight) = flights.get(key) {
+            return flight.subscribe();
+        }
+        let (sender, receiver) = broadcast::channel(1);
+        flights.insert(key.to_string(), sender.clone());
+        let guard = FlightGuard {
+            key: key.to_string(),
+            flights: self.flights.clone(),
+        };
+        // the span is a child of the request that started the flight, so its queries
+        // show up in that request's trace
+        let span = info_span!("single_flight", key);
+        self.tasks.spawn(
+            async move {
+                let result = compute.await;
+                // waiters subscribed while the flight was registered, so they all get the result
+                drop(guard);
+                let _ = sender.send(result);
+            }
+            .instrument(span),
+        );
+        receiver
+    }
+}
+
+// Unregisters a flight once its computation ends. Also runs when the computation
+// panics, dropping the last senders so waiters see the channel close instead of hanging.
+struct FlightGuard {
+    key: String,
+    flights: Flights,
+}
+impl Drop for FlightGuard {
+    fn drop(&mut self) {
+        lock(&self.flights).remove(&self.key);
+    }
+}
+
+fn lock(
+    flights: &Flights,
+) -> std::sync::MutexGuard<'_, HashMap<String, broadcast::Sender<FlightResult>>> {
+    flights
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tokio::sync::Notify;
+
+    use super::*;
+
+    // A computation of `result` that waits for `release` and counts its runs
+    fn compute(
+        runs: &Arc<AtomicUsize>,
+        release: &Arc<Notify>,
+        result: FlightResult,
+    ) -> impl Future<Output = FlightResult> + Send + 'static {
+        let runs = runs.clone();
+        let release = release.clone();
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            release.notified().await;
+            result
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_computation() {
+        let flights = SingleFlight::new(TaskTracker::new());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+        let receivers: Vec<_> = (0..5)
+            .map(|_| flights.start("key", compute(&runs, &release, Ok("value".to_string()))))
+            .collect();
+        // another key isn't coalesced with it
+        let mut other = flights.start("other", compute(&runs, &release, Ok("other".to_string())));
+        while runs.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+        release.notify_waiters();
+        for mut receiver in receivers {
+            assert_eq!(receiver.recv().await.unwrap(), Ok("value".to_string()));
+        }
+        assert_eq!(other.recv().await.unwrap(), Ok("other".to_string()));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn errors_reach_every_waiter() {
+        let flights = Arc::new(SingleFlight::new(TaskTracker::new()));
+        let runs = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+        let error = (StatusCode::INTERNAL_SERVER_ERROR, "db down".to_string());
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let flights = flights.clone();
+                let compute = compute(&runs, &release, Err(error.clone()));
+                tokio::spawn(async move { flights.run("key", compute).await })
+            })
+            .collect();
+        while runs.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+        // every waiter has subscribed once the tasks have been polled
+        tokio::task::yield_now().await;
+        release.notify_waiters();
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap(), Err(error.clone()));
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn finished_flights_are_forgotten() {
+        let flights = SingleFlight::new(TaskTracker::new());
+        for value in ["first", "second"] {
+            let result = flights
+                .run("key", async move { Ok(value.to_string()) })
+                .await;
+            assert_eq!(result, Ok(value.to_string()));
+        }
+        assert!(lock(&flights.flights).is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_panicking_computation_fails_its_waiters() {
+        let flights = SingleFlight::new(TaskTracker::new());
+        let result = flights
+            .run("key", async { panic!("computation panicked") })
+            .await;
+        assert_eq!(result.unwrap_err().0, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(lock(&flights.flights).is_empty());
+    }
+
+    // The computation outlives the caller that started it
+    #[tokio::test]
+    async fn cancelled_callers_dont_abort_the_computation() {
+        let tasks = TaskTracker::new();
+        let flights = SingleFlight::new(tasks.clone());
+        let runs = Arc::new(AtomicUsize::new(0));
+        let release = Arc::new(Notify::new());
+        let first = flights.run("key", compute(&runs, &release, Ok("value".to_string())));
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(10), first)
+                .await
+                .is_err()
+        );
+        let mut waiter = flights.start("key", compute(&runs, &release, Ok("again".to_string())));
+        release.notify_waiters();
+        assert_eq!(waiter.recv().await.unwrap(), Ok("value".to_string()));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use sqlx::migrate::Migrator;
+
+use crate::models::{
+    request::{self, bulk},
+    Admin, Category, IdempotencyKey, Product, Synonym,
+};
+
+pub mod postgres;
+pub mod sqlite;
+
+// Storage of the catalogue. Handlers only talk to these traits, the backend is
+// chosen by the scheme of `DATABASE_URL` (`sqlite:` or `postgres://`).
+#[async_trait]
+pub trait Database:
+    ProductRepo + CategoryRepo + AdminRepo + SearchRepo + BulkRepo + IdempotencyRepo
+{
+    // Applies the backend's migration set
+    async fn migrate(&self) -> anyhow::Result<()>;
+    async fn ping(&self) -> Result<(), sqlx::Error>;
+    // Version of the newest applied migration, None on an empty database
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error>;
+    // Version of the newest migration shipped with this build
+    fn latest_migration(&self) -> i64;
+    fn pool_status(&self) -> PoolStatus;
+    // Waits for checked out connections to be returned, then closes the pool
+    async fn close(&self);
+    // Number of products and categories
+    async fn catalogue_counts(&self) -> Result<(i64, i64), sqlx::Error>;
+}
+
+// Connections of the database pool
+#[derive(Debug, Clone, Copy)]
+pub struct PoolStatus {
+    // open connections, idle or in use
+    pub size: u32,
+    pub idle: u32,
+    pub max: u32,
+}
+
+pub async fn connect(url: &str, max_connections: u32) -> anyhow::Result<Box<dyn Database>> {
+    if url.starts_with("sqlite:") {
+        Ok(Box::new(
+            sqlite::SqliteDatabase::connect(url, max_connections).await?,
+        ))
+    } else if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        Ok(Box::new(
+            postgres::PgDatabase::connect(url, max_connections).await?,
+        ))
+    } else {
+        anyhow::bail!("unsupported database URL, expected sqlite: or postgres://")
+    }
+}
+
+fn latest_migration(migrator: &Migrator) -> i64 {
+    migrator
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+#[async_trait]
+pub trait ProductRepo: Send + Sync {
+    async fn product(&self, id: i64) -> Result<Option<Product>, sqlx::Error>;
+    // Products of `ids` that exist, in no particular order
+    async fn products_in(&self, ids: &[i64]) -> Result<Vec<Product>, sqlx::Error>;
+    // Products ordered by id
+    async fn product_page(&self, limit: i64, offset: i64) -> Result<Vec<Product>, sqlx::Error>;
+    // Products whose name contains at least one term of every group, see `expand_query`
+    async fn search_products(
+        &self,
+        term_groups: &[Vec<String>],
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Product>, sqlx::Error>;
+    async fn create_product(&self, product: &request::create::Product) -> Result<(), sqlx::Error>;
+    async fn update_product(&self, product: &Product) -> Result<(), sqlx::Error>;
+    async fn delete_product(&self, id: i64) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait CategoryRepo: Send + Sync {
+    // Top-level categories
+    async fn parent_categories(&self) -> Result<Vec<Category>, sqlx::Error>;
+    // Products of a category and of every category in its subtree
+    async fn category_products(&self, category_id: i64) -> Result<Vec<Product>, sqlx::Error>;
+    // Every category in the subtree of a category, without the category itself
+    async fn sub_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error>;
+    // Every ancestor of a category
+    async fn ancestor_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error>;
+    // The categories among `ids`
+    async fn categories_in(&self, ids: &[i64]) -> Result<Vec<Category>, sqlx::Error>;
+    // Direct children of the categories among `parent_ids`
+    async fn child_categories_in(&self, parent_ids: &[i64]) -> Result<Vec<Category>, sqlx::Error>;
+    // `ancestor_categories` of several categories at once, as (category id, ancestor)
+    // pairs ordered from the root down
+    async fn ancestor_categories_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Category)>, sqlx::Error>;
+    // `category_products` of several categories at once, as (category id, product) pairs
+    async fn category_products_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Product)>, sqlx::Error>;
+    async fn create_category(
+        &self,
+        category: &request::create::Category,
+    ) -> Result<(), sqlx::Error>;
+    async fn update_category(&self, category: &Category) -> Result<(), sqlx::Error>;
+    async fn delete_category(&self, id: i64) -> Result<(), sqlx::Error>;
+}
+
+// Writes of several products and categories at once. Each operation returns the
+// id of the row it created, None for updates and deletes, and updates or deletes
+// of a missing row fail with `RowNotFound`.
+#[async_trait]
+pub trait BulkRepo: Send + Sync {
+    // Runs `operations` in order in one transaction, so either all of them are
+    // applied or none is. Fails with the index of the operation that failed.
+    async fn apply_all(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Result<Vec<Option<i64>>, (usize, sqlx::Error)>;
+    // Runs `operations` in order, each on its own whatever the others do
+    async fn apply_each(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Vec<Result<Option<i64>, sqlx::Error>>;
+}
+
+// Responses of the mutating admin routes, replayed to the retries of a request
+#[async_trait]
+// Keys are scoped by `client`, the same key sent by two clients is two keys
+pub trait IdempotencyRepo: Send + Sync {
+    // Claims `key` for a request, after forgetting the keys created before
+    // `expired_before` and the claims still running since before `abandoned_before`.
+    // Returns false when the key is already claimed.
+    async fn claim_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        fingerprint: &str,
+        created_at: i64,
+        expired_before: i64,
+        abandoned_before: i64,
+    ) -> Result<bool, sqlx::Error>;
+    async fn idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error>;
+    // Stores the response of the request that claimed `key`
+    async fn complete_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        status: i64,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), sqlx::Error>;
+    // Forgets `key`, so a retry runs the request again
+    async fn release_idempotency_key(&self, client: &str, key: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait AdminRepo: Send + Sync {
+    async fn admin_by_username(&self, username: &str) -> Result<Option<Admin>, sqlx::Error>;
+    // `password` is the bcrypt hash
+    async fn create_admin(&self, username: &str, password: &str) -> Result<(), sqlx::Error>;
+    async fn create_token(&self, token: &str, admin_id: i64) -> Result<(), sqlx::Error>;
+    // Id of the admin the session token was issued to
+    async fn token_admin(&self, token: &str) -> Result<Option<i64>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SearchRepo: Send + Sync {
+    // Every synonym ordered by group then term
+    async fn synonyms(&self) -> Result<Vec<Synonym>, sqlx::Error>;
+    // The synonyms among `terms`, whatever their group
+    async fn synonyms_in(&self, terms: &[String]) -> Result<Vec<Synonym>, sqlx::Error>;
+    async fn group_terms(&self, group_id: i64) -> Result<Vec<String>, sqlx::Error>;
+    // Returns the id of the new group
+    async fn create_synonym_group(&self, terms: &[String]) -> Result<i64, sqlx::Error>;
+    async fn replace_synonym_group(
+        &self,
+        group_id: i64,
+        terms: &[String],
+    ) -> Result<(), sqlx::Error>;
+    async fn delete_synonym_group(&self, group_id: i64) -> Result<(), sqlx::Error>;
+    async fn stop_words(&self) -> Result<Vec<String>, sqlx::Error>;
+    // Adding an existing stop word is a no-op
+    async fn create_stop_word(&self, word: &str) -> Result<(), sqlx::Error>;
+    async fn delete_stop_word(&self, word: &str) -> Result<(), sqlx::Error>;
+}
@@ -0,0 +1,728 @@
+use async_trait::async_trait;
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, types::Json, Executor, PgPool, Postgres};
+use tracing::instrument;
+
+use crate::db::{
+    latest_migration, AdminRepo, BulkRepo, CategoryRepo, Database, IdempotencyRepo, PoolStatus,
+    ProductRepo, SearchRepo,
+};
+use crate::models::{
+    request::{self, bulk},
+    Admin, Category, CategoryAncestor, CategoryProduct, IdempotencyKey, Product, Synonym,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+// The query macros are checked against the single `DATABASE_URL` the crate is built
+// with (the SQLite catalogue), so Postgres queries are unchecked runtime queries.
+pub struct PgDatabase {
+    pool: PgPool,
+    max_connections: u32,
+}
+impl PgDatabase {
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = PgPoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+        Ok(Self {
+            pool,
+            max_connections,
+        })
+    }
+}
+
+#[async_trait]
+impl Database for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn migrate(&self) -> anyhow::Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&self.pool)
+            .await
+    }
+    fn latest_migration(&self) -> i64 {
+        latest_migration(&MIGRATOR)
+    }
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.max_connections,
+        }
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn catalogue_counts(&self) -> Result<(i64, i64), sqlx::Error> {
+        sqlx::query_as("SELECT (SELECT COUNT(*) FROM products), (SELECT COUNT(*) FROM categories)")
+            .fetch_one(&self.pool)
+            .await
+    }
+}
+
+#[async_trait]
+impl ProductRepo for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn product(&self, id: i64) -> Result<Option<Product>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, name, description, price, category_id FROM products WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn products_in(&self, ids: &[i64]) -> Result<Vec<Product>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, name, description, price, category_id FROM products WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn product_page(&self, limit: i64, offset: i64) -> Result<Vec<Product>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+        SELECT
+            id,
+            name,
+            description,
+            price,
+            category_id
+        FROM products
+        ORDER BY id
+        LIMIT $1 OFFSET $2
+        "#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn search_products(
+        &self,
+        term_groups: &[Vec<String>],
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Product>, sqlx::Error> {
+        // ILIKE matches SQLite's case-insensitive LIKE
+        sqlx::query_as(
+            r#"
+    SELECT
+        id,
+        name,
+        description,
+        price,
+        category_id
+    FROM products
+    WHERE NOT EXISTS (
+        SELECT 1
+        FROM jsonb_array_elements($1) term_group
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM jsonb_array_elements_text(term_group) term
+            WHERE products.name ILIKE '%' || term || '%'
+        )
+    )
+    ORDER BY id
+    LIMIT $2 OFFSET $3
+    "#,
+        )
+        .bind(Json(term_groups))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn create_product(&self, product: &request::create::Product) -> Result<(), sqlx::Error> {
+        insert_product(&self.pool, product).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn update_product(&self, product: &Product) -> Result<(), sqlx::Error> {
+        update_product(&self.pool, product).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn delete_product(&self, id: i64) -> Result<(), sqlx::Error> {
+        delete_product(&self.pool, id).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn parent_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, name, description, parent_id FROM categories WHERE parent_id IS NULL",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn category_products(&self, category_id: i64) -> Result<Vec<Product>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id
+        FROM categories
+        WHERE id = $1
+
+        UNION ALL
+
+        SELECT c.id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT
+        p.id,
+        p.name,
+        p.description,
+        p.price,
+        p.category_id
+    FROM products p
+    JOIN subcategories sc ON p.category_id = sc.id
+    ORDER BY p.id
+    "#,
+        )
+        .bind(category_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn sub_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id, name, description, parent_id
+        FROM categories
+        WHERE parent_id = $1
+
+        UNION ALL
+
+        SELECT c.id, c.name, c.description, c.parent_id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT id, name, description, parent_id
+    FROM subcategories
+    ORDER BY id
+    "#,
+        )
+        .bind(category_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn ancestor_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+    WITH RECURSIVE parent_categories AS (
+        SELECT id, name, description, parent_id
+        FROM categories
+        WHERE id = $1
+
+        UNION ALL
+
+        SELECT c.id, c.name, c.description, c.parent_id
+        FROM categories c
+        JOIN parent_categories pc ON pc.parent_id = c.id
+    )
+    SELECT
+        id,
+        name,
+        description,
+        parent_id
+    FROM parent_categories
+    WHERE id != $1
+    ORDER BY id
+    "#,
+        )
+        .bind(category_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn categories_in(&self, ids: &[i64]) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as("SELECT id, name, description, parent_id FROM categories WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn child_categories_in(&self, parent_ids: &[i64]) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, name, description, parent_id FROM categories WHERE parent_id = ANY($1) ORDER BY id",
+        )
+        .bind(parent_ids)
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn ancestor_categories_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Category)>, sqlx::Error> {
+        let rows: Vec<CategoryAncestor> = sqlx::query_as(
+            r#"
+    WITH RECURSIVE ancestors AS (
+        SELECT id AS of_category, parent_id AS id, 0 AS depth
+        FROM categories
+        WHERE id = ANY($1) AND parent_id IS NOT NULL
+
+        UNION ALL
+
+        SELECT a.of_category, c.parent_id, a.depth + 1
+        FROM categories c
+        JOIN ancestors a ON c.id = a.id
+        WHERE c.parent_id IS NOT NULL
+    )
+    SELECT
+        a.of_category,
+        c.id,
+        c.name,
+        c.description,
+        c.parent_id
+    FROM ancestors a
+    JOIN categories c ON c.id = a.id
+    ORDER BY a.of_category, a.depth DESC
+    "#,
+        )
+        .bind(category_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn category_products_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Product)>, sqlx::Error> {
+        let rows: Vec<CategoryProduct> = sqlx::query_as(
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id AS of_category, id
+        FROM categories
+        WHERE id = ANY($1)
+
+        UNION ALL
+
+        SELECT sc.of_category, c.id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT
+        sc.of_category,
+        p.id,
+        p.name,
+        p.description,
+        p.price,
+        p.category_id
+    FROM products p
+    JOIN subcategories sc ON p.category_id = sc.id
+    ORDER BY sc.of_category, p.id
+    "#,
+        )
+        .bind(category_ids)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn create_category(
+        &self,
+        category: &request::create::Category,
+    ) -> Result<(), sqlx::Error> {
+        insert_category(&self.pool, category).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn update_category(&self, category: &Category) -> Result<(), sqlx::Error> {
+        update_category(&self.pool, category).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn delete_category(&self, id: i64) -> Result<(), sqlx::Error> {
+        delete_category(&self.pool, id).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkRepo for PgDatabase {
+    #[instrument(skip_all, fields(db.system = "postgresql", operations = operations.len()))]
+    async fn apply_all(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Result<Vec<Option<i64>>, (usize, sqlx::Error)> {
+        let mut tx = self.pool.begin().await.map_err(|err| (0, err))?;
+        let mut ids = Vec::with_capacity(operations.len());
+        for (index, operation) in operations.iter().enumerate() {
+            // dropping the transaction rolls it back
+            ids.push(
+                apply(&mut tx, operation)
+                    .await
+                    .map_err(|err| (index, err))?,
+            );
+        }
+        tx.commit().await.map_err(|err| (operations.len(), err))?;
+        Ok(ids)
+    }
+    #[instrument(skip_all, fields(db.system = "postgresql", operations = operations.len()))]
+    async fn apply_each(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Vec<Result<Option<i64>, sqlx::Error>> {
+        let mut results = Vec::with_capacity(operations.len());
+        for operation in operations {
+            results.push(apply(&self.pool, operation).await);
+        }
+        results
+    }
+}
+
+// Runs one bulk operation on the pool or in a transaction
+async fn apply<'e, E>(executor: E, operation: &bulk::Operation) -> Result<Option<i64>, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let touched = match operation {
+        bulk::Operation::CreateProduct(product) => {
+            return insert_product(executor, product).await.map(Some)
+        }
+        bulk::Operation::CreateCategory(category) => {
+            return insert_category(executor, category).await.map(Some)
+        }
+        bulk::Operation::UpdateProduct(product) => update_product(executor, product).await?,
+        bulk::Operation::DeleteProduct(product) => {
+            delete_product(executor, product.product_id).await?
+        }
+        bulk::Operation::UpdateCategory(category) => update_category(executor, category).await?,
+        bulk::Operation::DeleteCategory(category) => {
+            delete_category(executor, category.category_id).await?
+        }
+    };
+    if touched == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(None)
+}
+// The catalogue writes, shared by the single-item and the bulk methods. Inserts
+// return the new id, updates and deletes the number of rows they touched.
+async fn insert_product<'e, E>(
+    executor: E,
+    product: &request::create::Product,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "INSERT INTO products (name, description, price, category_id) VALUES($1, $2, $3, $4)
+        RETURNING id",
+    )
+    .bind(&product.name)
+    .bind(&product.description)
+    .bind(product.price)
+    .bind(product.category_id)
+    .fetch_one(executor)
+    .await
+}
+async fn update_product<'e, E>(executor: E, product: &Product) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query(
+        "UPDATE products SET name = $1, description = $2, price = $3, category_id = $4
+        WHERE id = $5",
+    )
+    .bind(&product.name)
+    .bind(&product.description)
+    .bind(product.price)
+    .bind(product.category_id)
+    .bind(product.id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+async fn delete_product<'e, E>(executor: E, id: i64) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query("DELETE FROM products WHERE id = $1")
+        .bind(id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected())
+}
+async fn insert_category<'e, E>(
+    executor: E,
+    category: &request::create::Category,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query_scalar(
+        "INSERT INTO categories (name, description, parent_id) VALUES($1, $2, $3) RETURNING id",
+    )
+    .bind(&category.name)
+    .bind(&category.description)
+    .bind(category.parent_id)
+    .fetch_one(executor)
+    .await
+}
+async fn update_category<'e, E>(executor: E, category: &Category) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query(
+        "UPDATE categories SET name = $1, description = $2, parent_id = $3 WHERE id = $4",
+    )
+    .bind(&category.name)
+    .bind(&category.description)
+    .bind(category.parent_id)
+    .bind(category.id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+async fn delete_category<'e, E>(executor: E, id: i64) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let result = sqlx::query("DELETE FROM categories WHERE id = $1")
+        .bind(id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[async_trait]
+impl IdempotencyRepo for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn claim_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        fingerprint: &str,
+        created_at: i64,
+        expired_before: i64,
+        abandoned_before: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE created_at < $1 OR (status IS NULL AND created_at < $2)",
+        )
+        .bind(expired_before)
+        .bind(abandoned_before)
+        .execute(&mut tx)
+        .await?;
+        let claimed = sqlx::query(
+            "INSERT INTO idempotency_keys (client, key, fingerprint, created_at)
+            VALUES ($1, $2, $3, $4) ON CONFLICT (client, key) DO NOTHING",
+        )
+        .bind(client)
+        .bind(key)
+        .bind(fingerprint)
+        .bind(created_at)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(claimed == 1)
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT fingerprint, status::BIGINT AS status, content_type, body
+            FROM idempotency_keys WHERE client = $1 AND key = $2",
+        )
+        .bind(client)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+    }
+    #[instrument(skip(self, body), fields(db.system = "postgresql"), err)]
+    async fn complete_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        status: i64,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE idempotency_keys SET status = $1, content_type = $2, body = $3
+            WHERE client = $4 AND key = $5",
+        )
+        .bind(status as i32)
+        .bind(content_type)
+        .bind(body)
+        .bind(client)
+        .bind(key)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn release_idempotency_key(&self, client: &str, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE client = $1 AND key = $2")
+            .bind(client)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AdminRepo for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn admin_by_username(&self, username: &str) -> Result<Option<Admin>, sqlx::Error> {
+        sqlx::query_as("SELECT username, password, id FROM admins WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await
+    }
+    #[instrument(skip(self, password), fields(db.system = "postgresql"), err)]
+    async fn create_admin(&self, username: &str, password: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO admins (username, password) VALUES($1, $2)")
+            .bind(username)
+            .bind(password)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self, token), fields(db.system = "postgresql"), err)]
+    async fn create_token(&self, token: &str, admin_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO tokens (token, admin_id) VALUES($1, $2)")
+            .bind(token)
+            .bind(admin_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self, token), fields(db.system = "postgresql"), err)]
+    async fn token_admin(&self, token: &str) -> Result<Option<i64>, sqlx::Error> {
+        let admin_id: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT admin_id FROM tokens WHERE token = $1")
+                .bind(token)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(admin_id.flatten())
+    }
+}
+
+#[async_trait]
+impl SearchRepo for PgDatabase {
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn synonyms(&self) -> Result<Vec<Synonym>, sqlx::Error> {
+        sqlx::query_as("SELECT term, group_id FROM synonyms ORDER BY group_id, term")
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn synonyms_in(&self, terms: &[String]) -> Result<Vec<Synonym>, sqlx::Error> {
+        sqlx::query_as("SELECT term, group_id FROM synonyms WHERE term = ANY($1)")
+            .bind(terms)
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn group_terms(&self, group_id: i64) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT term FROM synonyms WHERE group_id = $1")
+            .bind(group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn create_synonym_group(&self, terms: &[String]) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let group_id: i64 =
+            sqlx::query_scalar("INSERT INTO synonym_groups DEFAULT VALUES RETURNING id")
+                .fetch_one(&mut tx)
+                .await?;
+        for term in terms {
+            sqlx::query("INSERT INTO synonyms (term, group_id) VALUES($1, $2)")
+                .bind(term)
+                .bind(group_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(group_id)
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn replace_synonym_group(
+        &self,
+        group_id: i64,
+        terms: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM synonyms WHERE group_id = $1")
+            .bind(group_id)
+            .execute(&mut tx)
+            .await?;
+        for term in terms {
+            sqlx::query("INSERT INTO synonyms (term, group_id) VALUES($1, $2)")
+                .bind(term)
+                .bind(group_id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn delete_synonym_group(&self, group_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM synonyms WHERE group_id = $1")
+            .bind(group_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM synonym_groups WHERE id = $1")
+            .bind(group_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn stop_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT word FROM stop_words ORDER BY word")
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn create_stop_word(&self, word: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO stop_words (word) VALUES($1) ON CONFLICT DO NOTHING")
+            .bind(word)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "postgresql"), err)]
+    async fn delete_stop_word(&self, word: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM stop_words WHERE word = $1")
+            .bind(word)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
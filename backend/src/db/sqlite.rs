@@ -0,0 +1,769 @@
+use async_trait::async_trait;
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, Executor, Sqlite, SqlitePool};
+use tracing::instrument;
+
+use crate::db::{
+    latest_migration, AdminRepo, BulkRepo, CategoryRepo, Database, IdempotencyRepo, PoolStatus,
+    ProductRepo, SearchRepo,
+};
+use crate::models::{
+    request::{self, bulk},
+    Admin, Category, CategoryAncestor, CategoryProduct, IdempotencyKey, Product, SubCategory,
+    Synonym,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+pub struct SqliteDatabase {
+    pool: SqlitePool,
+    max_connections: u32,
+}
+impl SqliteDatabase {
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self, sqlx::Error> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(max_connections)
+            .connect(url)
+            .await?;
+        Ok(Self {
+            pool,
+            max_connections,
+        })
+    }
+}
+
+#[async_trait]
+impl Database for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn migrate(&self) -> anyhow::Result<()> {
+        MIGRATOR.run(&self.pool).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn schema_version(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+            .fetch_one(&self.pool)
+            .await
+    }
+    fn latest_migration(&self) -> i64 {
+        latest_migration(&MIGRATOR)
+    }
+    async fn close(&self) {
+        self.pool.close().await;
+    }
+    fn pool_status(&self) -> PoolStatus {
+        PoolStatus {
+            size: self.pool.size(),
+            idle: self.pool.num_idle() as u32,
+            max: self.max_connections,
+        }
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn catalogue_counts(&self) -> Result<(i64, i64), sqlx::Error> {
+        let counts = sqlx::query!(
+            r#"SELECT
+                (SELECT COUNT(*) FROM products) AS "products!: i64",
+                (SELECT COUNT(*) FROM categories) AS "categories!: i64""#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((counts.products, counts.categories))
+    }
+}
+
+#[async_trait]
+impl ProductRepo for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn product(&self, id: i64) -> Result<Option<Product>, sqlx::Error> {
+        sqlx::query_as!(Product, "SELECT * from products WHERE id = ?", id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn products_in(&self, ids: &[i64]) -> Result<Vec<Product>, sqlx::Error> {
+        let ids_json =
+            serde_json::to_string(ids).map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        sqlx::query_as!(
+            Product,
+            "SELECT * FROM products WHERE id IN (SELECT value FROM json_each(?))",
+            ids_json
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn product_page(&self, limit: i64, offset: i64) -> Result<Vec<Product>, sqlx::Error> {
+        sqlx::query_as!(
+            Product,
+            r#"
+        SELECT
+            id,
+            name,
+            description,
+            price,
+            category_id
+        FROM products
+        ORDER BY id
+        LIMIT ? OFFSET ?
+        "#,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn search_products(
+        &self,
+        term_groups: &[Vec<String>],
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Product>, sqlx::Error> {
+        let term_groups = serde_json::to_string(term_groups)
+            .map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        sqlx::query_as!(
+            Product,
+            r#"
+    SELECT
+        id,
+        name,
+        description,
+        price,
+        category_id
+    FROM products
+    WHERE NOT EXISTS (
+        SELECT 1
+        FROM json_each(?) term_group
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM json_each(term_group.value) term
+            WHERE products.name LIKE '%' || term.value || '%'
+        )
+    )
+    ORDER BY id
+    LIMIT ? OFFSET ?
+    "#,
+            term_groups,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn create_product(&self, product: &request::create::Product) -> Result<(), sqlx::Error> {
+        insert_product(&self.pool, product).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn update_product(&self, product: &Product) -> Result<(), sqlx::Error> {
+        update_product(&self.pool, product).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn delete_product(&self, id: i64) -> Result<(), sqlx::Error> {
+        delete_product(&self.pool, id).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn parent_categories(&self) -> Result<Vec<Category>, sqlx::Error> {
+        sqlx::query_as!(Category, "SELECT * from categories WHERE parent_id IS NULL")
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn category_products(&self, category_id: i64) -> Result<Vec<Product>, sqlx::Error> {
+        sqlx::query_as!(
+            Product,
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id
+        FROM categories
+        WHERE id = ?
+
+        UNION ALL
+
+        SELECT c.id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT
+        p.id,
+        p.name,
+        p.description,
+        p.price,
+        p.category_id
+    FROM products p
+    JOIN subcategories sc ON p.category_id = sc.id
+    ORDER BY p.id
+    "#,
+            category_id
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn sub_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error> {
+        Ok(sqlx::query_as!(
+            SubCategory,
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id, name, description, parent_id
+        FROM categories
+        WHERE parent_id = ?
+
+        UNION ALL
+
+        SELECT c.id, c.name, c.description, c.parent_id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT id, name, description, parent_id
+    FROM subcategories
+    ORDER BY id
+    "#,
+            category_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .filter_map(|sc| sc.try_into().ok())
+        .collect())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn ancestor_categories(&self, category_id: i64) -> Result<Vec<Category>, sqlx::Error> {
+        Ok(sqlx::query_as!(
+            SubCategory,
+            r#"
+    WITH RECURSIVE parent_categories AS (
+        SELECT id, name, description, parent_id
+        FROM categories
+        WHERE id = ?
+
+        UNION ALL
+
+        SELECT c.id, c.name, c.description, c.parent_id
+        FROM categories c
+        JOIN parent_categories pc ON pc.parent_id = c.id
+    )
+    SELECT
+        id,
+        name,
+        description,
+        parent_id
+    FROM parent_categories
+    WHERE id != ?
+    ORDER BY id
+    "#,
+            category_id,
+            category_id
+        )
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .filter_map(|sc| sc.try_into().ok())
+        .collect())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn categories_in(&self, ids: &[i64]) -> Result<Vec<Category>, sqlx::Error> {
+        let ids_json =
+            serde_json::to_string(ids).map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        sqlx::query_as!(
+            Category,
+            "SELECT * FROM categories WHERE id IN (SELECT value FROM json_each(?))",
+            ids_json
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn child_categories_in(&self, parent_ids: &[i64]) -> Result<Vec<Category>, sqlx::Error> {
+        let ids_json = serde_json::to_string(parent_ids)
+            .map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        sqlx::query_as!(
+            Category,
+            "SELECT * FROM categories WHERE parent_id IN (SELECT value FROM json_each(?)) ORDER BY id",
+            ids_json
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn ancestor_categories_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Category)>, sqlx::Error> {
+        let ids_json = serde_json::to_string(category_ids)
+            .map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        let rows = sqlx::query_as!(
+            CategoryAncestor,
+            r#"
+    WITH RECURSIVE ancestors AS (
+        SELECT id AS of_category, parent_id AS id, 0 AS depth
+        FROM categories
+        WHERE id IN (SELECT value FROM json_each(?)) AND parent_id IS NOT NULL
+
+        UNION ALL
+
+        SELECT a.of_category, c.parent_id, a.depth + 1
+        FROM categories c
+        JOIN ancestors a ON c.id = a.id
+        WHERE c.parent_id IS NOT NULL
+    )
+    SELECT
+        a.of_category AS "of_category!",
+        c.id AS "id!",
+        c.name,
+        c.description,
+        c.parent_id
+    FROM ancestors a
+    JOIN categories c ON c.id = a.id
+    ORDER BY a.of_category, a.depth DESC
+    "#,
+            ids_json
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn category_products_in(
+        &self,
+        category_ids: &[i64],
+    ) -> Result<Vec<(i64, Product)>, sqlx::Error> {
+        let ids_json = serde_json::to_string(category_ids)
+            .map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        let rows = sqlx::query_as!(
+            CategoryProduct,
+            r#"
+    WITH RECURSIVE subcategories AS (
+        SELECT id AS of_category, id
+        FROM categories
+        WHERE id IN (SELECT value FROM json_each(?))
+
+        UNION ALL
+
+        SELECT sc.of_category, c.id
+        FROM categories c
+        JOIN subcategories sc ON c.parent_id = sc.id
+    )
+    SELECT
+        sc.of_category AS "of_category!",
+        p.id AS "id!",
+        p.name,
+        p.description,
+        p.price,
+        p.category_id
+    FROM products p
+    JOIN subcategories sc ON p.category_id = sc.id
+    ORDER BY sc.of_category, p.id
+    "#,
+            ids_json
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn create_category(
+        &self,
+        category: &request::create::Category,
+    ) -> Result<(), sqlx::Error> {
+        insert_category(&self.pool, category).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn update_category(&self, category: &Category) -> Result<(), sqlx::Error> {
+        update_category(&self.pool, category).await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn delete_category(&self, id: i64) -> Result<(), sqlx::Error> {
+        delete_category(&self.pool, id).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BulkRepo for SqliteDatabase {
+    #[instrument(skip_all, fields(db.system = "sqlite", operations = operations.len()))]
+    async fn apply_all(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Result<Vec<Option<i64>>, (usize, sqlx::Error)> {
+        let mut tx = self.pool.begin().await.map_err(|err| (0, err))?;
+        let mut ids = Vec::with_capacity(operations.len());
+        for (index, operation) in operations.iter().enumerate() {
+            // dropping the transaction rolls it back
+            ids.push(
+                apply(&mut tx, operation)
+                    .await
+                    .map_err(|err| (index, err))?,
+            );
+        }
+        tx.commit().await.map_err(|err| (operations.len(), err))?;
+        Ok(ids)
+    }
+    #[instrument(skip_all, fields(db.system = "sqlite", operations = operations.len()))]
+    async fn apply_each(
+        &self,
+        operations: &[bulk::Operation],
+    ) -> Vec<Result<Option<i64>, sqlx::Error>> {
+        let mut results = Vec::with_capacity(operations.len());
+        for operation in operations {
+            results.push(apply(&self.pool, operation).await);
+        }
+        results
+    }
+}
+
+// Runs one bulk operation on the pool or in a transaction
+async fn apply<'e, E>(executor: E, operation: &bulk::Operation) -> Result<Option<i64>, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let touched = match operation {
+        bulk::Operation::CreateProduct(product) => {
+            return insert_product(executor, product).await.map(Some)
+        }
+        bulk::Operation::CreateCategory(category) => {
+            return insert_category(executor, category).await.map(Some)
+        }
+        bulk::Operation::UpdateProduct(product) => update_product(executor, product).await?,
+        bulk::Operation::DeleteProduct(product) => {
+            delete_product(executor, product.product_id).await?
+        }
+        bulk::Operation::UpdateCategory(category) => update_category(executor, category).await?,
+        bulk::Operation::DeleteCategory(category) => {
+            delete_category(executor, category.category_id).await?
+        }
+    };
+    if touched == 0 {
+        return Err(sqlx::Error::RowNotFound);
+    }
+    Ok(None)
+}
+// The catalogue writes, shared by the single-item and the bulk methods. Inserts
+// return the new id, updates and deletes the number of rows they touched.
+async fn insert_product<'e, E>(
+    executor: E,
+    product: &request::create::Product,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!(
+        "INSERT INTO products (name, description, price, category_id) VALUES(?, ?, ?, ?)",
+        product.name,
+        product.description,
+        product.price,
+        product.category_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+async fn update_product<'e, E>(executor: E, product: &Product) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!(
+        "UPDATE products SET name = ?, description = ?, price = ?, category_id = ? WHERE id = ?",
+        product.name,
+        product.description,
+        product.price,
+        product.category_id,
+        product.id
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+async fn delete_product<'e, E>(executor: E, id: i64) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!("DELETE FROM products WHERE id = ?", id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected())
+}
+async fn insert_category<'e, E>(
+    executor: E,
+    category: &request::create::Category,
+) -> Result<i64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!(
+        "INSERT INTO categories (name, description, parent_id) VALUES(?, ?, ?)",
+        category.name,
+        category.description,
+        category.parent_id
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+async fn update_category<'e, E>(executor: E, category: &Category) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!(
+        "UPDATE categories SET name = ?, description = ?, parent_id = ? WHERE id = ?",
+        category.name,
+        category.description,
+        category.parent_id,
+        category.id
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
+async fn delete_category<'e, E>(executor: E, id: i64) -> Result<u64, sqlx::Error>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    let result = sqlx::query!("DELETE FROM categories WHERE id = ?", id)
+        .execute(executor)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+#[async_trait]
+impl IdempotencyRepo for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn claim_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        fingerprint: &str,
+        created_at: i64,
+        expired_before: i64,
+        abandoned_before: i64,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "DELETE FROM idempotency_keys WHERE created_at < ? OR (status IS NULL AND created_at < ?)",
+            expired_before,
+            abandoned_before
+        )
+        .execute(&mut tx)
+        .await?;
+        let claimed = sqlx::query!(
+            "INSERT INTO idempotency_keys (client, key, fingerprint, created_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (client, key) DO NOTHING",
+            client,
+            key,
+            fingerprint,
+            created_at
+        )
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        tx.commit().await?;
+        Ok(claimed == 1)
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+    ) -> Result<Option<IdempotencyKey>, sqlx::Error> {
+        sqlx::query_as!(
+            IdempotencyKey,
+            "SELECT fingerprint, status, content_type, body FROM idempotency_keys
+            WHERE client = ? AND key = ?",
+            client,
+            key
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    #[instrument(skip(self, body), fields(db.system = "sqlite"), err)]
+    async fn complete_idempotency_key(
+        &self,
+        client: &str,
+        key: &str,
+        status: i64,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE idempotency_keys SET status = ?, content_type = ?, body = ?
+            WHERE client = ? AND key = ?",
+            status,
+            content_type,
+            body,
+            client,
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn release_idempotency_key(&self, client: &str, key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "DELETE FROM idempotency_keys WHERE client = ? AND key = ?",
+            client,
+            key
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AdminRepo for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn admin_by_username(&self, username: &str) -> Result<Option<Admin>, sqlx::Error> {
+        sqlx::query_as!(
+            Admin,
+            "SELECT username, password, id FROM admins WHERE username = ?",
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+    #[instrument(skip(self, password), fields(db.system = "sqlite"), err)]
+    async fn create_admin(&self, username: &str, password: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO admins (username, password) VALUES(?, ?)",
+            username,
+            password
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    #[instrument(skip(self, token), fields(db.system = "sqlite"), err)]
+    async fn create_token(&self, token: &str, admin_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO tokens (token, admin_id) VALUES(?, ?)",
+            token,
+            admin_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+    #[instrument(skip(self, token), fields(db.system = "sqlite"), err)]
+    async fn token_admin(&self, token: &str) -> Result<Option<i64>, sqlx::Error> {
+        let admin_id = sqlx::query_scalar!("SELECT admin_id FROM tokens WHERE token = ?", token)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(admin_id.flatten())
+    }
+}
+
+#[async_trait]
+impl SearchRepo for SqliteDatabase {
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn synonyms(&self) -> Result<Vec<Synonym>, sqlx::Error> {
+        sqlx::query_as!(
+            Synonym,
+            "SELECT term, group_id FROM synonyms ORDER BY group_id, term"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn synonyms_in(&self, terms: &[String]) -> Result<Vec<Synonym>, sqlx::Error> {
+        let terms_json =
+            serde_json::to_string(terms).map_err(|err| sqlx::Error::Protocol(err.to_string()))?;
+        sqlx::query_as!(
+            Synonym,
+            "SELECT term, group_id FROM synonyms WHERE term IN (SELECT value FROM json_each(?))",
+            terms_json
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn group_terms(&self, group_id: i64) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!("SELECT term FROM synonyms WHERE group_id = ?", group_id)
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn create_synonym_group(&self, terms: &[String]) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let group_id = sqlx::query!("INSERT INTO synonym_groups DEFAULT VALUES")
+            .execute(&mut tx)
+            .await?
+            .last_insert_rowid();
+        for term in terms {
+            sqlx::query!(
+                "INSERT INTO synonyms (term, group_id) VALUES(?, ?)",
+                term,
+                group_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(group_id)
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn replace_synonym_group(
+        &self,
+        group_id: i64,
+        terms: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM synonyms WHERE group_id = ?", group_id)
+            .execute(&mut tx)
+            .await?;
+        for term in terms {
+            sqlx::query!(
+                "INSERT INTO synonyms (term, group_id) VALUES(?, ?)",
+                term,
+                group_id
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn delete_synonym_group(&self, group_id: i64) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM synonyms WHERE group_id = ?", group_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query!("DELETE FROM synonym_groups WHERE id = ?", group_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn stop_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar!("SELECT word FROM stop_words ORDER BY word")
+            .fetch_all(&self.pool)
+            .await
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn create_stop_word(&self, word: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("INSERT OR IGNORE INTO stop_words (word) VALUES(?)", word)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+    #[instrument(skip(self), fields(db.system = "sqlite"), err)]
+    async fn delete_stop_word(&self, word: &str) -> Result<(), sqlx::Error> {
+        sqlx::query!("DELETE FROM stop_words WHERE word = ?", word)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
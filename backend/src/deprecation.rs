@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use tracing::info;
+
+use crate::AppState;
+
+pub const DEPRECATION_HEADER: &str = "deprecation";
+pub const SUNSET_HEADER: &str = "sunset";
+
+// When the unversioned routes were deprecated in favour of /api/v1, as the
+// `@<unix time>` of the Deprecation header (RFC 9745): 19 Oct 2026
+const LEGACY_DEPRECATED_AT: &str = "@1792368000";
+
+// Marks the unversioned aliases of /api/v1 deprecated: announces the deprecation,
+// the configured sunset and the versioned route replacing them, and records who
+// still calls them
+pub async fn legacy_alias(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let method = request.method().clone();
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+    let successor = format!(
+        "</api/v1{}>; rel=\"successor-version\"",
+        request.uri().path()
+    );
+    info!(
+        %route,
+        %user_agent,
+        "deprecated unversioned route called, use /api/v1{}",
+        route
+    );
+    app_state
+        .metrics
+        .deprecated_request(method.as_str(), &route);
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(
+        HeaderName::from_static(DEPRECATION_HEADER),
+        HeaderValue::from_static(LEGACY_DEPRECATED_AT),
+    );
+    if let Ok(sunset) = HeaderValue::from_str(&app_state.config.api.legacy_sunset) {
+        headers.insert(HeaderName::from_static(SUNSET_HEADER), sunset);
+    }
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.append(header::LINK, link);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::StatusCode,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{config::Config, routes};
+
+    async fn get(app: &Router, uri: &str) -> Response {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    // The deprecated requests counted for `route`
+    async fn deprecated_requests(app: &Router, route: &str) -> u64 {
+        let response = get(app, "/metrics").await;
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let series = format!("deprecated_requests_total{{method=\"GET\",route=\"{route}\"}} ");
+        String::from_utf8(body.to_vec())
+            .unwrap()
+            .lines()
+            .find_map(|line| line.strip_prefix(&series))
+            .map_or(0, |count| count.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn only_unversioned_aliases_are_deprecated() {
+        let config = Config::default();
+        let sunset = config.api.legacy_sunset.clone();
+        let state = Arc::new(AppState::for_tests(config).await);
+        let app = routes::router(&state);
+
+        let response = get(&app, "/categories").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[DEPRECATION_HEADER], LEGACY_DEPRECATED_AT);
+        assert_eq!(headers[SUNSET_HEADER], sunset.as_str());
+        assert_eq!(
+            headers[header::LINK],
+            "</api/v1/categories>; rel=\"successor-version\""
+        );
+        assert_eq!(deprecated_requests(&app, "/categories").await, 1);
+
+        let response = get(&app, "/api/v1/categories").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        for name in [DEPRECATION_HEADER, SUNSET_HEADER, header::LINK.as_str()] {
+            assert!(response.headers().get(name).is_none(), "{name}");
+        }
+        assert_eq!(deprecated_requests(&app, "/categories").await, 1);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use axum::http::StatusCode;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use utoipa::IntoParams;
+
+use crate::{
+    handlers::internal_error,
+    models::{Category, Product},
+    AppState,
+};
+
+// Fields of `Product` that `?fields=` can keep
+const PRODUCT_FIELDS: [&str; 5] = ["id", "name", "description", "price", "category_id"];
+
+// `?include=` and `?fields=` of the catalogue reads. Both apply to every product
+// of the response, the cached response is shaped after it is read.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExpandParams {
+    /// Comma separated entities to embed in each product: `category`, and the
+    /// `ancestors` of its category from the top-level category down
+    #[param(example = "category,ancestors")]
+    include: Option<String>,
+    /// Comma separated product fields to keep, all of them by default. Embedded
+    /// entities are kept whatever the fields.
+    #[param(example = "id,name,price")]
+    fields: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Expansion {
+    category: bool,
+    ancestors: bool,
+    fields: Option<Vec<String>>,
+}
+impl TryFrom<ExpandParams> for Expansion {
+    type Error = (StatusCode, String);
+    fn try_from(params: ExpandParams) -> Result<Self, Self::Error> {
+        let mut expansion = Expansion::default();
+        for include in list(params.include.as_deref()) {
+            match include {
+                "category" => expansion.category = true,
+                "ancestors" => expansion.ancestors = true,
+                other => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        format!("unknown include {other:?}, expected category or ancestors"),
+                    ))
+                }
+            }
+        }
+        let mut fields: Vec<String> = Vec::new();
+        for field in list(params.fields.as_deref()) {
+            if !PRODUCT_FIELDS.contains(&field) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "unknown product field {field:?}, expected some of {}",
+                        PRODUCT_FIELDS.join(",")
+                    ),
+                ));
+            }
+            if !fields.iter().any(|kept| kept == field) {
+                fields.push(field.to_string());
+            }
+        }
+        // an empty list keeps every field, like no list
+        if !fields.is_empty() {
+            expansion.fields = Some(fields);
+        }
+        Ok(expansion)
+    }
+}
+impl Expansion {
+    // The JSON of `products` with the included entities embedded and the fields
+    // trimmed. Each include costs one query for all the products.
+    pub async fn products(
+        &self,
+        products: Vec<Product>,
+        app_state: &AppState,
+    ) -> Result<Vec<Value>, (StatusCode, String)> {
+        let mut category_ids: Vec<i64> = products
+            .iter()
+            .filter_map(|product| product.category_id)
+            .collect();
+        category_ids.sort_unstable();
+        category_ids.dedup();
+        let categories: HashMap<i64, Category> = if self.category && !category_ids.is_empty() {
+            app_state
+                .db
+                .categories_in(&category_ids)
+                .await
+                .map_err(internal_error)?
+                .into_iter()
+                .map(|category| (category.id, category))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        let mut ancestors: HashMap<i64, Vec<Category>> = HashMap::new();
+        if self.ancestors && !category_ids.is_empty() {
+            let rows = app_state
+                .db
+                .ancestor_categories_in(&category_ids)
+                .await
+                .map_err(internal_error)?;
+            for (category_id, ancestor) in rows {
+                ancestors.entry(category_id).or_default().push(ancestor);
+            }
+        }
+        products
+            .into_iter()
+            .map(|product| {
+                let category_id = product.category_id;
+                let mut object = match serde_json::to_value(product).map_err(internal_error)? {
+                    Value::Object(object) => object,
+                    _ => unreachable!("products serialize to objects"),
+                };
+                if let Some(fields) = &self.fields {
+                    object.retain(|field, _| fields.contains(field));
+                }
+                if self.category {
+                    let category = category_id.and_then(|id| categories.get(&id));
+                    insert(&mut object, "category", category)?;
+                }
+                if self.ancestors {
+                    let ancestors = category_id
+                        .and_then(|id| ancestors.get(&id))
+                        .map(Vec::as_slice)
+                        .unwrap_or_default();
+                    insert(&mut object, "ancestors", ancestors)?;
+                }
+                Ok(Value::Object(object))
+            })
+            .collect()
+    }
+}
+
+fn insert<T: serde::Serialize>(
+    object: &mut Map<String, Value>,
+    key: &str,
+    value: T,
+) -> Result<(), (StatusCode, String)> {
+    object.insert(
+        key.to_string(),
+        serde_json::to_value(value).map_err(internal_error)?,
+    );
+    Ok(())
+}
+
+// Items of a comma separated list, without blanks
+fn list(value: Option<&str>) -> impl Iterator<Item = &str> {
+    value
+        .into_iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::Config;
+
+    fn expansion(include: Option<&str>, fields: Option<&str>) -> Result<Expansion, String> {
+        let params = ExpandParams {
+            include: include.map(str::to_string),
+            fields: fields.map(str::to_string),
+        };
+        Expansion::try_from(params).map_err(|(status, message)| {
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            message
+        })
+    }
+
+    #[test]
+    fn lists_are_trimmed_and_deduplicated() {
+        let expansion = expansion(
+            Some(" category , ,ancestors,category"),
+            Some("name, id ,name,"),
+        )
+        .unwrap();
+        assert!(expansion.category && expansion.ancestors);
+        assert_eq!(expansion.fields.unwrap(), ["name", "id"]);
+    }
+
+    #[test]
+    fn empty_lists_expand_nothing_and_keep_every_field() {
+        for list in [None, Some(""), Some(" , ")] {
+            let expansion = expansion(list, list).unwrap();
+            assert!(!expansion.category && !expansion.ancestors, "{list:?}");
+            assert!(expansion.fields.is_none(), "{list:?}");
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_refused() {
+        let err = expansion(Some("category,brand"), None).unwrap_err();
+        assert!(err.contains("\"brand\""), "{err}");
+        let err = expansion(None, Some("name,Price")).unwrap_err();
+        assert!(err.contains("\"Price\""), "{err}");
+        // names are matched whole and case sensitively
+        assert!(expansion(Some("Category"), None).is_err());
+        assert!(expansion(None, Some("name price")).is_err());
+    }
+
+    #[tokio::test]
+    async fn fields_are_kept_in_products() {
+        let state = AppState::for_tests(Config::default()).await;
+        let product = Product {
+            id: 1,
+            name: "Tee".to_string(),
+            description: None,
+            price: 10,
+            category_id: None,
+        };
+        let expansion = expansion(None, Some("price,id")).unwrap();
+        let products = expansion.products(vec![product], &state).await.unwrap();
+        assert_eq!(products, [json!({ "id": 1, "price": 10 })]);
+    }
+}
@@ -0,0 +1,454 @@
+use std::sync::Arc;
+
+use async_graphql::{
+    http::GraphiQLSource, ComplexObject, Context, EmptySubscription, Error, ErrorExtensions, Guard,
+    Object, Result, Schema,
+};
+use axum::{
+    extract::{FromRequest, Request, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Extension, Json,
+};
+
+use crate::{
+    handlers::{admin, common, internal_error},
+    models::{request, Category, Product},
+    rate_limit::{self, RateScope},
+    AppState,
+};
+
+pub mod loaders;
+
+use loaders::Loaders;
+
+// Refused before anything runs, so one request can't walk the whole category tree
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 500;
+
+pub type CatalogueSchema = Schema<Query, Mutation, EmptySubscription>;
+
+pub fn schema() -> CatalogueSchema {
+    Schema::build(Query, Mutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+// Rate limit key of the client making the request
+struct Client(String);
+// Admin session token the request carries, as for the `/admin` routes
+struct SessionToken(Option<String>);
+
+// GET /graphql -> 200 GraphiQL
+pub async fn graphiql() -> Html<String> {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+// POST /graphql { query, variables, operationName } -> 200 { data, errors }, 400
+pub async fn execute(
+    State(app_state): State<Arc<AppState>>,
+    Extension(schema): Extension<CatalogueSchema>,
+    request: Request,
+) -> Response {
+    let client = rate_limit::client_key(&request, &app_state.config.rate_limit);
+    let session_token = admin::session_token(request.headers()).map(str::to_string);
+    let graphql_request = match Json::<async_graphql::Request>::from_request(request, &()).await {
+        Ok(Json(graphql_request)) => graphql_request,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let graphql_request = graphql_request
+        .data(Loaders::new(&app_state))
+        .data(Client(client))
+        .data(SessionToken(session_token))
+        .data(app_state);
+    Json(schema.execute(graphql_request).await).into_response()
+}
+
+// Charges a top-level field to the rate limit budget of the REST route it stands
+// for, so a query costs what the REST calls it replaces would
+struct Budget(RateScope);
+impl Guard for Budget {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let Client(client) = ctx.data::<Client>()?;
+        match rate_limit::acquire(app_state, self.0, client)
+            .await
+            .map_err(handler_error)?
+        {
+            Some(decision) if !decision.allowed => Err(Error::new(format!(
+                "rate limit exceeded, retry in {} seconds",
+                decision.retry_after
+            ))
+            .extend_with(|_, extensions| {
+                extensions.set("status", StatusCode::TOO_MANY_REQUESTS.as_u16());
+                extensions.set("retryAfter", decision.retry_after);
+            })),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Refuses a field without a valid admin session, like `admin::admin_auth` does
+struct Admin;
+impl Guard for Admin {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let SessionToken(token) = ctx.data::<SessionToken>()?;
+        let admin_id = match token {
+            Some(token) => app_state.db.token_admin(token).await.map_err(db_error)?,
+            None => None,
+        };
+        match admin_id {
+            Some(_) => Ok(()),
+            None => Err(handler_error((
+                StatusCode::UNAUTHORIZED,
+                "Not logged in".to_string(),
+            ))),
+        }
+    }
+}
+
+// The error of a handler, with its HTTP status as the `status` extension
+fn handler_error((status, message): (StatusCode, String)) -> Error {
+    Error::new(message).extend_with(|_, extensions| extensions.set("status", status.as_u16()))
+}
+
+fn db_error<E: std::error::Error>(err: E) -> Error {
+    handler_error(internal_error(err))
+}
+
+pub struct Query;
+#[Object]
+impl Query {
+    #[graphql(guard = "Budget(RateScope::Read)")]
+    async fn product(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Product>> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        app_state.db.product(id).await.map_err(db_error)
+    }
+    // Zero-based page of `catalogue.page_size` products
+    #[graphql(guard = "Budget(RateScope::Read)")]
+    async fn products(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] page: i64,
+    ) -> Result<Vec<Product>> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let products = common::cached_product_page(app_state, page)
+            .await
+            .map_err(handler_error)?;
+        Ok(products.value)
+    }
+    // Products matching every term of `query`, expanded with synonyms
+    #[graphql(guard = "Budget(RateScope::Search)")]
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        #[graphql(default)] page: i64,
+    ) -> Result<Vec<Product>> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let products = common::cached_search(app_state, &query, page)
+            .await
+            .map_err(handler_error)?;
+        Ok(products.value)
+    }
+    #[graphql(guard = "Budget(RateScope::Read)")]
+    async fn category(&self, ctx: &Context<'_>, id: i64) -> Result<Option<Category>> {
+        let loaders = ctx.data::<Loaders>()?;
+        loaders.categories.load_one(id).await.map_err(db_error)
+    }
+    // Top-level categories
+    #[graphql(guard = "Budget(RateScope::Read)")]
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let categories = common::cached_parent_categories(app_state)
+            .await
+            .map_err(handler_error)?;
+        Ok(categories.value)
+    }
+}
+
+#[ComplexObject]
+impl Category {
+    // Direct subcategories
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let loaders = ctx.data::<Loaders>()?;
+        let children = loaders.children.load_one(self.id).await.map_err(db_error)?;
+        Ok(children.unwrap_or_default())
+    }
+    async fn parent(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        let Some(parent_id) = self.parent_id else {
+            return Ok(None);
+        };
+        let loaders = ctx.data::<Loaders>()?;
+        loaders
+            .categories
+            .load_one(parent_id)
+            .await
+            .map_err(db_error)
+    }
+    // From the top-level category down to the parent, the breadcrumbs of the category
+    async fn ancestors(&self, ctx: &Context<'_>) -> Result<Vec<Category>> {
+        let loaders = ctx.data::<Loaders>()?;
+        let ancestors = loaders
+            .ancestors
+            .load_one(self.id)
+            .await
+            .map_err(db_error)?;
+        Ok(ancestors.unwrap_or_default())
+    }
+    // Products of the category and of its whole subtree, as on `GET /category/{id}`
+    async fn products(&self, ctx: &Context<'_>) -> Result<Vec<Product>> {
+        let loaders = ctx.data::<Loaders>()?;
+        let products = loaders.products.load_one(self.id).await.map_err(db_error)?;
+        Ok(products.unwrap_or_default())
+    }
+}
+
+#[ComplexObject]
+impl Product {
+    async fn category(&self, ctx: &Context<'_>) -> Result<Option<Category>> {
+        let Some(category_id) = self.category_id else {
+            return Ok(None);
+        };
+        let loaders = ctx.data::<Loaders>()?;
+        loaders
+            .categories
+            .load_one(category_id)
+            .await
+            .map_err(db_error)
+    }
+}
+
+// The catalogue administration of `/admin`, run by the same handlers so
+// validation and cache invalidation can't drift apart. Like those routes each
+// field draws from the admin budget and requires an admin session. Unlike them
+// mutations don't take an `Idempotency-Key`, a retried request runs again.
+pub struct Mutation;
+#[Object]
+impl Mutation {
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn create_category(
+        &self,
+        ctx: &Context<'_>,
+        category: request::create::Category,
+    ) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        admin::create_category(State(app_state.clone()), Json(category))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn update_category(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        category: request::create::Category,
+    ) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let category = Category {
+            id,
+            name: category.name,
+            description: category.description,
+            parent_id: category.parent_id,
+        };
+        admin::update_category(State(app_state.clone()), Json(category))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn delete_category(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let category = request::delete::Category { category_id: id };
+        admin::delete_category(State(app_state.clone()), Json(category))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn create_product(
+        &self,
+        ctx: &Context<'_>,
+        product: request::create::Product,
+    ) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        admin::create_product(State(app_state.clone()), Json(product))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn update_product(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        product: request::create::Product,
+    ) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let product = Product {
+            id,
+            name: product.name,
+            description: product.description,
+            price: product.price,
+            category_id: product.category_id,
+        };
+        admin::update_product(State(app_state.clone()), Json(product))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+    #[graphql(guard = "Budget(RateScope::Admin).and(Admin)")]
+    async fn delete_product(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        let app_state = ctx.data::<Arc<AppState>>()?;
+        let product = request::delete::Product { product_id: id };
+        admin::delete_product(State(app_state.clone()), Json(product))
+            .await
+            .map_err(handler_error)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::config::Config;
+
+    const TOKEN: &str = "session";
+
+    // Shirts with its subcategory Tees holding one product, and an admin signed in with `TOKEN`
+    async fn state() -> Arc<AppState> {
+        let mut config = Config::default();
+        config.rate_limit.enabled = false;
+        let state = AppState::for_tests(config).await;
+        for (name, parent_id) in [("Shirts", None), ("Tees", Some(1))] {
+            let category = request::create::Category {
+                name: name.to_string(),
+                description: None,
+                parent_id,
+            };
+            state.db.create_category(&category).await.unwrap();
+        }
+        let product = request::create::Product {
+            name: "Plain tee".to_string(),
+            description: None,
+            price: 10,
+            category_id: Some(2),
+        };
+        state.db.create_product(&product).await.unwrap();
+        state.db.create_admin("admin", "").await.unwrap();
+        let admin = state.db.admin_by_username("admin").await.unwrap().unwrap();
+        state.db.create_token(TOKEN, admin.id).await.unwrap();
+        Arc::new(state)
+    }
+
+    // Runs `query` as `execute` would, with the session token given
+    async fn run(state: &Arc<AppState>, query: &str, token: Option<&str>) -> Value {
+        let request = async_graphql::Request::new(query)
+            .data(Loaders::new(state))
+            .data(Client("test".to_string()))
+            .data(SessionToken(token.map(str::to_string)))
+            .data(state.clone());
+        serde_json::to_value(schema().execute(request).await).unwrap()
+    }
+
+    fn error_status(response: &Value) -> &Value {
+        &response["errors"][0]["extensions"]["status"]
+    }
+
+    const CREATE: &str =
+        r#"mutation { createCategory(category: { name: "Shoes", parentId: null }) }"#;
+
+    #[tokio::test]
+    async fn mutations_need_an_admin_session() {
+        let state = state().await;
+        for token in [None, Some("unknown")] {
+            let response = run(&state, CREATE, token).await;
+            assert_eq!(error_status(&response), 401, "{response}");
+        }
+        assert!(state.db.categories_in(&[3]).await.unwrap().is_empty());
+        let response = run(&state, CREATE, Some(TOKEN)).await;
+        assert_eq!(
+            response["data"],
+            json!({ "createCategory": true }),
+            "{response}"
+        );
+        assert_eq!(state.db.categories_in(&[3]).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn categories_resolve_their_relations() {
+        let state = state().await;
+        let query = r#"{
+            categories {
+                name
+                children { name parent { name } ancestors { name } products { name } }
+                products { name category { name } }
+            }
+        }"#;
+        let response = run(&state, query, None).await;
+        assert!(response.get("errors").is_none(), "{response}");
+        let expected = json!({
+            "categories": [{
+                "name": "Shirts",
+                "children": [{
+                    "name": "Tees",
+                    "parent": { "name": "Shirts" },
+                    "ancestors": [{ "name": "Shirts" }],
+                    "products": [{ "name": "Plain tee" }],
+                }],
+                // the whole subtree's products
+                "products": [{ "name": "Plain tee", "category": { "name": "Tees" } }],
+            }]
+        });
+        assert_eq!(response["data"], expected);
+    }
+
+    #[tokio::test]
+    async fn loaders_batch_lookups() {
+        let state = state().await;
+        let loaders = Loaders::new(&state);
+        let (shirts, tees, missing) = tokio::join!(
+            loaders.categories.load_one(1),
+            loaders.categories.load_one(2),
+            loaders.categories.load_one(99),
+        );
+        assert_eq!(shirts.unwrap().unwrap().name, "Shirts");
+        assert_eq!(tees.unwrap().unwrap().name, "Tees");
+        assert!(missing.unwrap().is_none());
+        let children = loaders.children.load_many([1, 2]).await.unwrap();
+        assert_eq!(children[&1].len(), 1);
+        assert!(children.get(&2).is_none_or(Vec::is_empty));
+    }
+
+    #[tokio::test]
+    async fn overly_deep_or_complex_queries_are_refused() {
+        let state = state().await;
+        let deep = format!(
+            "{{ category(id: 1) {{ {} name {} }} }}",
+            "children {".repeat(MAX_DEPTH),
+            "}".repeat(MAX_DEPTH)
+        );
+        let response = run(&state, &deep, None).await;
+        assert!(response["data"].is_null(), "{response}");
+        assert!(response["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("nested too deep"));
+        // a product costs one plus one per field
+        let fields = (0..MAX_COMPLEXITY / 5 + 1)
+            .map(|i| format!("p{i}: product(id: 1) {{ id name description price }}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let response = run(&state, &format!("{{ {fields} }}"), None).await;
+        assert!(response["data"].is_null(), "{response}");
+        assert!(response["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("too complex"));
+    }
+}
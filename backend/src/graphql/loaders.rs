@@ -0,0 +1,90 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_graphql::dataloader::{DataLoader, Loader};
+
+use crate::{
+    models::{Category, Product},
+    AppState,
+};
+
+// Batches the lookups resolvers make while one GraphQL request runs: every
+// category of a response asking for its children, ancestors or products costs
+// one query per field instead of one per category
+pub struct Loaders {
+    pub categories: DataLoader<CategoryLoader>,
+    pub children: DataLoader<ChildrenLoader>,
+    pub ancestors: DataLoader<AncestorsLoader>,
+    pub products: DataLoader<CategoryProductsLoader>,
+}
+impl Loaders {
+    pub fn new(app_state: &Arc<AppState>) -> Self {
+        Self {
+            categories: DataLoader::new(CategoryLoader(app_state.clone()), tokio::spawn),
+            children: DataLoader::new(ChildrenLoader(app_state.clone()), tokio::spawn),
+            ancestors: DataLoader::new(AncestorsLoader(app_state.clone()), tokio::spawn),
+            products: DataLoader::new(CategoryProductsLoader(app_state.clone()), tokio::spawn),
+        }
+    }
+}
+
+// sqlx errors aren't Clone, which loaders need to hand one error to every caller
+type LoadError = Arc<sqlx::Error>;
+
+// Categories by id
+pub struct CategoryLoader(Arc<AppState>);
+impl Loader<i64> for CategoryLoader {
+    type Value = Category;
+    type Error = LoadError;
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Category>, LoadError> {
+        let categories = self.0.db.categories_in(ids).await?;
+        Ok(categories
+            .into_iter()
+            .map(|category| (category.id, category))
+            .collect())
+    }
+}
+
+// Direct subcategories by parent id
+pub struct ChildrenLoader(Arc<AppState>);
+impl Loader<i64> for ChildrenLoader {
+    type Value = Vec<Category>;
+    type Error = LoadError;
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<Category>>, LoadError> {
+        let mut children: HashMap<i64, Vec<Category>> = HashMap::new();
+        for category in self.0.db.child_categories_in(ids).await? {
+            if let Some(parent_id) = category.parent_id {
+                children.entry(parent_id).or_default().push(category);
+            }
+        }
+        Ok(children)
+    }
+}
+
+// Ancestors by category id, from the top-level category down
+pub struct AncestorsLoader(Arc<AppState>);
+impl Loader<i64> for AncestorsLoader {
+    type Value = Vec<Category>;
+    type Error = LoadError;
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<Category>>, LoadError> {
+        Ok(group(self.0.db.ancestor_categories_in(ids).await?))
+    }
+}
+
+// Products of the subtree of a category by category id
+pub struct CategoryProductsLoader(Arc<AppState>);
+impl Loader<i64> for CategoryProductsLoader {
+    type Value = Vec<Product>;
+    type Error = LoadError;
+    async fn load(&self, ids: &[i64]) -> Result<HashMap<i64, Vec<Product>>, LoadError> {
+        Ok(group(self.0.db.category_products_in(ids).await?))
+    }
+}
+
+// Groups tagged rows by their tag, keeping their order
+fn group<T>(rows: Vec<(i64, T)>) -> HashMap<i64, Vec<T>> {
+    let mut groups: HashMap<i64, Vec<T>> = HashMap::new();
+    for (id, row) in rows {
+        groups.entry(id).or_default().push(row);
+    }
+    groups
+}
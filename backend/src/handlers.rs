@@ -1,14 +1,19 @@
-use std::sync::Arc;
+use std::{collections::HashSet, future::Future, sync::Arc, time::Duration};
 
 use axum::http::StatusCode;
-use redis::AsyncCommands;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use tracing::info;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::{debug, info, warn};
 
-use crate::AppState;
+use crate::{
+    cache::{CacheEntry, CacheKey, CacheTag},
+    search::query_mentions,
+    AppState,
+};
 
 pub mod admin;
 pub mod common;
+pub mod health;
+pub mod v2;
 pub type HandlerResult<T> = Result<(StatusCode, T), (StatusCode, String)>;
 pub fn internal_error<E>(err: E) -> (StatusCode, String)
 where
@@ -17,45 +22,185 @@ where
     (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
 
-pub async fn redis_get<T>(
-    key: &String,
-    app_state: &Arc<AppState>,
-) -> Result<Option<T>, (StatusCode, String)>
+// Cache failures never fail a request: reads fall back to the database and
+// writes are skipped, both with a warning.
+pub async fn cache_get<T>(key: &str, app_state: &Arc<AppState>) -> Option<T>
+where
+    T: DeserializeOwned,
+{
+    let json = match app_state.cache.get(key).await {
+        Ok(json) => json,
+        Err(err) => {
+            warn!(
+                "cache read of {} failed, using the database: {:#}",
+                key, err
+            );
+            app_state.metrics.cache_lookup(key, "error");
+            return None;
+        }
+    };
+    parse_cached(key, json, app_state)
+}
+// `cache_get` of several keys in one round trip, `None` for each key that
+// missed. A failed read misses every key.
+pub async fn cache_get_many<T>(keys: &[String], app_state: &Arc<AppState>) -> Vec<Option<T>>
+where
+    T: DeserializeOwned,
+{
+    match app_state.cache.get_many(keys).await {
+        Ok(jsons) => keys
+            .iter()
+            .zip(jsons)
+            .map(|(key, json)| parse_cached(key, json, app_state))
+            .collect(),
+        Err(err) => {
+            warn!(
+                "cache read of {} keys failed, using the database: {:#}",
+                keys.len(),
+                err
+            );
+            for key in keys {
+                app_state.metrics.cache_lookup(key, "error");
+            }
+            keys.iter().map(|_| None).collect()
+        }
+    }
+}
+fn parse_cached<T>(key: &str, json: Option<String>, app_state: &Arc<AppState>) -> Option<T>
 where
     T: DeserializeOwned,
 {
-    let mut redis_conn = app_state.redis.get().await.map_err(internal_error)?;
-
-    let json: Option<String> = redis_conn.get(key).await.map_err(internal_error)?;
     if let Some(ref json) = json {
-        info!("obtained: {}", json);
+        debug!("cache hit on {} ({} bytes)", key, json.len());
     } else {
-        info!("obtained none");
+        debug!("cache miss on {}", key);
+        app_state.metrics.cache_lookup(key, "miss");
     }
-    match json {
-        Some(value) => {
-            let parsed = serde_json::from_str(&value).map_err(internal_error)?;
-            Ok(Some(parsed))
+    match serde_json::from_str(&json?) {
+        Ok(parsed) => {
+            app_state.metrics.cache_lookup(key, "hit");
+            Some(parsed)
+        }
+        Err(err) => {
+            app_state.metrics.cache_lookup(key, "error");
+            warn!(
+                "cached value of {} is malformed, using the database: {}",
+                key, err
+            );
+            None
         }
-        None => Ok(None),
     }
 }
-pub async fn redis_set<T>(
-    key: String,
+pub async fn cache_set<T>(
+    key: &str,
     value: &T,
+    ttl: Duration,
+    tags: &[CacheTag],
     app_state: &Arc<AppState>,
-) -> Result<(), (StatusCode, String)>
+) where
+    T: Serialize,
+{
+    let json = match serde_json::to_string(value) {
+        Ok(json) => json,
+        Err(err) => {
+            warn!("failed to serialize {} for the cache: {}", key, err);
+            return;
+        }
+    };
+    if let Err(err) = app_state.cache.set(key, json, ttl, tags).await {
+        warn!("cache write of {} failed: {:#}", key, err);
+    }
+}
+// Serves `key` from the cache, computing it with `load` on a miss.
+// Concurrent misses of one key share a single `load`, and an expired entry is
+// still served during the stale window while one background task refreshes it.
+pub async fn cached<T, F>(
+    key: String,
+    ttl: Duration,
+    load: F,
+    app_state: &Arc<AppState>,
+) -> Result<CacheEntry<T>, (StatusCode, String)>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: Future<Output = Result<(T, Vec<CacheTag>), (StatusCode, String)>> + Send + 'static,
+{
+    let refresh = refresh(key.clone(), ttl, load, app_state.clone());
+    if let Some(entry) = cache_get::<CacheEntry<T>>(&key, app_state).await {
+        if entry.is_fresh() {
+            debug!("using the cached response of {}", key);
+        } else {
+            info!("Using stale cached response of {} while refreshing it", key);
+            app_state.flights.start(&key, refresh);
+        }
+        return Ok(entry);
+    }
+    let json = app_state.flights.run(&key, refresh).await?;
+    serde_json::from_str(&json).map_err(internal_error)
+}
+// Recomputes `key` with `load` and stores it even when a fresh entry exists,
+// joining a computation of the key that is already running
+pub async fn cache_refresh<T, F>(
+    key: String,
+    ttl: Duration,
+    load: F,
+    app_state: &Arc<AppState>,
+) -> Result<T, (StatusCode, String)>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + 'static,
+    F: Future<Output = Result<(T, Vec<CacheTag>), (StatusCode, String)>> + Send + 'static,
+{
+    let refresh = refresh(key.clone(), ttl, load, app_state.clone());
+    let json = app_state.flights.run(&key, refresh).await?;
+    let entry: CacheEntry<T> = serde_json::from_str(&json).map_err(internal_error)?;
+    Ok(entry.value)
+}
+async fn refresh<T, F>(
+    key: String,
+    ttl: Duration,
+    load: F,
+    app_state: Arc<AppState>,
+) -> Result<String, (StatusCode, String)>
 where
     T: Serialize,
+    F: Future<Output = Result<(T, Vec<CacheTag>), (StatusCode, String)>>,
 {
-    let mut redis_conn = app_state.redis.get().await.map_err(internal_error)?;
-
-    let json = serde_json::to_string(value).map_err(internal_error)?;
-
-    redis_conn
-        .set_ex::<_, _, ()>(key, json, 300)
-        .await
-        .map_err(internal_error)?;
-
-    Ok(())
+    let (value, tags) = load.await.inspect_err(|(_, err)| {
+        warn!("computing {} failed: {}", key, err);
+    })?;
+    let entry = CacheEntry::new(value, ttl);
+    // the entry outlives its TTL by the stale window so it can be served while refreshing
+    let expiry = ttl + app_state.config.cache.ttl.stale;
+    cache_set(&key, &entry, expiry, &tags, &app_state).await;
+    serde_json::to_string(&entry).map_err(internal_error)
+}
+// Deletes every cached response registered under any of `tags`
+pub async fn cache_invalidate(tags: &[CacheTag], app_state: &Arc<AppState>) {
+    match app_state.cache.invalidate(tags).await {
+        Ok(invalidated) => info!(
+            "invalidated {} cached responses for {:?}",
+            invalidated, tags
+        ),
+        Err(err) => warn!("cache invalidation of {:?} failed: {:#}", tags, err),
+    }
+}
+// Deletes cached `/product/search` responses whose query mentions any of `terms`
+pub async fn cache_invalidate_search(terms: &HashSet<String>, app_state: &Arc<AppState>) {
+    let keys = match app_state.cache.keys("/product/search").await {
+        Ok(keys) => keys,
+        Err(err) => {
+            warn!("listing cached searches failed: {:#}", err);
+            return;
+        }
+    };
+    let keys: Vec<String> = keys
+        .into_iter()
+        .filter(|key| {
+            let query = CacheKey::param_of(key, "query").unwrap_or_default();
+            query_mentions(&query, terms)
+        })
+        .collect();
+    info!("invalidating {} cached searches", keys.len());
+    if let Err(err) = app_state.cache.delete(&keys).await {
+        warn!("cache invalidation of searches failed: {:#}", err);
+    }
 }
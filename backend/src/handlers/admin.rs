@@ -1,48 +1,170 @@
-use std::sync::Arc;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-use crate::handlers::{internal_error, HandlerResult};
+use crate::handlers::{cache_invalidate, cache_invalidate_search, internal_error, HandlerResult};
 use crate::{
+    cache::CacheTag,
+    db::Database,
     generate_token,
-    models::{Admin, Category, Product},
+    models::{
+        request::{self, bulk},
+        Category, Product, SynonymGroup,
+    },
+    search::normalise_term,
+    warm::{self, WarmProgress, WarmTrigger},
     AppState,
 };
 use axum::http::HeaderMap;
 use axum::{
-    extract::{Path, State},
-    http::{header, HeaderName, StatusCode},
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
     Json,
 };
-use bcrypt::{hash, hash_with_salt, DEFAULT_COST};
-use serde::Deserialize;
-use sqlx::{Pool, SqlitePool};
+use bcrypt::hash_with_salt;
+use serde::{Deserialize, Serialize};
 use tokio::task::spawn_blocking;
 use tracing::info;
+use utoipa::{
+    openapi::{
+        security::{
+            ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityRequirement, SecurityScheme,
+        },
+        OpenApi, ResponseBuilder,
+    },
+    ToSchema,
+};
 
 const SALT_SIZE: usize = 16;
+// Cookie the login sets to the session token, for the whole site so `/graphql`
+// gets it too
+pub const AUTH_COOKIE: &str = "auth_token";
+const AUTH_COOKIE_ATTRIBUTES: &str = "Path=/; HttpOnly; SameSite=Lax";
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct LoginPayload {
     username: String,
     password: String,
 }
-// admin middleware
-pub async fn admin_auth() {}
+// Admin a request is signed in as, handed to the admin handlers by `admin_auth`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdminSession {
+    pub admin_id: i64,
+}
+// Session token of a request: an `Authorization: Bearer` header, or the cookie set
+// by the login
+pub fn session_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .and_then(|authorization| authorization.strip_prefix("Bearer "));
+    bearer.or_else(|| {
+        headers
+            .get_all(header::COOKIE)
+            .iter()
+            .filter_map(|cookies| cookies.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .find_map(|cookie| cookie.trim().strip_prefix(AUTH_COOKIE)?.strip_prefix('='))
+    })
+}
+// None without a session token, or with one no admin was issued
+pub async fn session(
+    app_state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<AdminSession>, sqlx::Error> {
+    let Some(token) = session_token(headers) else {
+        return Ok(None);
+    };
+    let admin_id = app_state.db.token_admin(token).await?;
+    Ok(admin_id.map(|admin_id| AdminSession { admin_id }))
+}
+// admin middleware: 401 without a valid session, the handlers get its `AdminSession`
+pub async fn admin_auth(
+    State(app_state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match session(&app_state, request.headers()).await {
+        Ok(Some(admin)) => {
+            request.extensions_mut().insert(admin);
+            next.run(request).await
+        }
+        Ok(None) => (StatusCode::UNAUTHORIZED, "Not logged in".to_string()).into_response(),
+        Err(err) => internal_error(err).into_response(),
+    }
+}
+// Documents the session every admin route but the login requires, and its 401
+pub fn document(api: &mut OpenApi) {
+    api.components
+        .get_or_insert_with(Default::default)
+        .add_security_schemes_from_iter([
+            (
+                "session_cookie",
+                SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(AUTH_COOKIE))),
+            ),
+            (
+                "session_bearer",
+                SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+            ),
+        ]);
+    for (_, item) in api
+        .paths
+        .paths
+        .iter_mut()
+        .filter(|(path, _)| path.contains("/admin/") && !path.ends_with("/admin/login"))
+    {
+        for operation in [
+            &mut item.get,
+            &mut item.post,
+            &mut item.patch,
+            &mut item.put,
+            &mut item.delete,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            operation.security = Some(vec![
+                SecurityRequirement::new("session_cookie", Vec::<String>::new()),
+                SecurityRequirement::new("session_bearer", Vec::<String>::new()),
+            ]);
+            operation.responses.responses.insert(
+                "401".to_string(),
+                ResponseBuilder::new()
+                    .description("Not logged in, or the session token is unknown")
+                    .build()
+                    .into(),
+            );
+        }
+    }
+}
 // POST /admin/login { username, password } -> 200 { SET_COOKIE: session_token }, 400
+#[utoipa::path(
+    post,
+    path = "/login",
+    tag = "admin",
+    request_body = LoginPayload,
+    responses(
+        (status = 200, description = "Logged in, the session token is set in the `auth_token` cookie",
+            headers(("set-cookie" = String))),
+        (status = 400, description = "Invalid credentials"),
+    )
+)]
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
     Json(req): Json<LoginPayload>,
 ) -> HandlerResult<HeaderMap> {
     // first check if username & password are valid
-    let res = sqlx::query_as!(
-        Admin,
-        "SELECT username, password, id FROM admins WHERE username = ?",
-        req.username
-    )
-    .fetch_optional(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    let res = app_state
+        .db
+        .admin_by_username(&req.username)
+        .await
+        .map_err(internal_error)?;
 
     let Some(found) = res else {
+        app_state.metrics.login(false);
         return Err((StatusCode::BAD_REQUEST, "Invalid credentials".to_string()));
     };
     let authenticated = spawn_blocking(async move || bcrypt::verify(req.password, &found.password))
@@ -51,21 +173,19 @@ pub async fn login(
         .await
         .map_err(internal_error)?;
     // compare
+    app_state.metrics.login(authenticated);
     if !authenticated {
         return Err((StatusCode::BAD_REQUEST, "Invalid credentials".to_string()));
     }
     // If auth is successful
     let token = generate_token();
-    sqlx::query!(
-        "INSERT INTO tokens (token, admin_id) VALUES(?, ?)",
-        token,
-        found.id
-    )
-    .execute(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    app_state
+        .db
+        .create_token(&token, found.id)
+        .await
+        .map_err(internal_error)?;
     let mut headers = HeaderMap::new();
-    if let Ok(cookie) = format!("auth_token={token}").parse() {
+    if let Ok(cookie) = format!("{AUTH_COOKIE}={token}; {AUTH_COOKIE_ATTRIBUTES}").parse() {
         headers.insert(header::SET_COOKIE, cookie);
     } else {
         return Err((
@@ -76,110 +196,1179 @@ pub async fn login(
     Ok((StatusCode::OK, headers))
 }
 pub async fn create_admin(
-    db_pool: SqlitePool,
+    db: &dyn Database,
     username: String,
     password: String,
+    cost: u32,
 ) -> anyhow::Result<()> {
     let salt = rand::random::<[u8; SALT_SIZE]>();
     let pass = password;
-    let hashed = spawn_blocking(async move || hash_with_salt(pass, DEFAULT_COST, salt))
+    let hashed = spawn_blocking(async move || hash_with_salt(pass, cost, salt))
         .await?
         .await?
         .to_string();
-    sqlx::query!(
-        "INSERT INTO admins (username, password) VALUES(?, ?)",
-        username,
-        hashed
-    )
-    .execute(&db_pool)
-    .await?;
+    db.create_admin(&username, &hashed).await?;
     Ok(())
 }
 // POST /admin/category { category } -> 200, 400, 401
+#[utoipa::path(
+    post,
+    path = "/category",
+    tag = "admin",
+    request_body = request::create::Category,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn create_category(
     State(app_state): State<Arc<AppState>>,
-    Json(category): Json<crate::models::request::create::Category>,
+    Json(category): Json<request::create::Category>,
 ) -> HandlerResult<()> {
-    sqlx::query!(
-        "INSERT INTO categories (name, description, parent_id) VALUES(?, ?, ?)",
-        category.name,
-        category.description,
-        category.parent_id
-    )
-    .execute(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    app_state
+        .db
+        .create_category(&category)
+        .await
+        .map_err(internal_error)?;
+    let tags = cache_tags(
+        &bulk::Operation::CreateCategory(category),
+        &Placement::default(),
+    );
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, ()))
 }
 // DELETE /admin/category { category_id } -> 200, 400, 401
+#[utoipa::path(
+    delete,
+    path = "/category",
+    tag = "admin",
+    request_body = request::delete::Category,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn delete_category(
     State(app_state): State<Arc<AppState>>,
-    Json(category): Json<crate::models::request::delete::Category>,
+    Json(category): Json<request::delete::Category>,
 ) -> HandlerResult<String> {
-    sqlx::query!("DELETE FROM categories WHERE id = ?", category.category_id)
-        .execute(&app_state.pg)
+    app_state
+        .db
+        .delete_category(category.category_id)
         .await
         .map_err(internal_error)?;
+    let tags = cache_tags(
+        &bulk::Operation::DeleteCategory(category),
+        &Placement::default(),
+    );
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, "ok".to_string()))
 }
 // PATCH /admin/category { category } -> 200, 400, 401
+#[utoipa::path(
+    patch,
+    path = "/category",
+    tag = "admin",
+    request_body = Category,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn update_category(
     State(app_state): State<Arc<AppState>>,
     Json(category): Json<Category>,
 ) -> HandlerResult<()> {
-    sqlx::query!(
-        "UPDATE categories SET name = ?, description = ?, parent_id = ? WHERE id = ?",
-        category.name,
-        category.description,
-        category.parent_id,
-        category.id
-    )
-    .execute(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    let parents = CategoryParents::load(&app_state, category.parent_id.as_slice()).await?;
+    if parents.creates_cycle(category.id, category.parent_id) {
+        return Err((StatusCode::BAD_REQUEST, cycle_error(category.id)));
+    }
+    let before = Placement::load(&app_state, &[], &[category.id]).await?;
+    app_state
+        .db
+        .update_category(&category)
+        .await
+        .map_err(internal_error)?;
+    let tags = cache_tags(&bulk::Operation::UpdateCategory(category), &before);
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, ()))
 }
 // POST /admin/product { product } -> 200, 400, 401
+#[utoipa::path(
+    post,
+    path = "/product",
+    tag = "admin",
+    request_body = request::create::Product,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn create_product(
     State(app_state): State<Arc<AppState>>,
-    Json(product): Json<crate::models::request::create::Product>,
+    Json(product): Json<request::create::Product>,
 ) -> HandlerResult<()> {
-    sqlx::query!(
-        "INSERT INTO products (name, description, price) VALUES(?, ?, ?)",
-        product.name,
-        product.description,
-        product.price
-    )
-    .execute(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    app_state
+        .db
+        .create_product(&product)
+        .await
+        .map_err(internal_error)?;
+    let tags = cache_tags(
+        &bulk::Operation::CreateProduct(product),
+        &Placement::default(),
+    );
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, ()))
 }
 // DELETE /admin/product/:product_id -> 200, 400, 401
+#[utoipa::path(
+    delete,
+    path = "/product",
+    tag = "admin",
+    request_body = request::delete::Product,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn delete_product(
     State(app_state): State<Arc<AppState>>,
-    Json(product): Json<crate::models::request::delete::Product>,
+    Json(product): Json<request::delete::Product>,
 ) -> HandlerResult<()> {
-    sqlx::query!("DELETE FROM products WHERE id = ?", product.product_id)
-        .execute(&app_state.pg)
+    app_state
+        .db
+        .delete_product(product.product_id)
         .await
         .map_err(internal_error)?;
+    let tags = cache_tags(
+        &bulk::Operation::DeleteProduct(product),
+        &Placement::default(),
+    );
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, ()))
 }
 // PATCH /admin/product { product } -> 200, 400, 401
+#[utoipa::path(
+    patch,
+    path = "/product",
+    tag = "admin",
+    request_body = Product,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 pub async fn update_product(
     State(app_state): State<Arc<AppState>>,
     Json(product): Json<Product>,
 ) -> HandlerResult<()> {
-    sqlx::query!(
-        "UPDATE products SET name = ?, description = ?, price = ? WHERE id = ?",
-        product.name,
-        product.description,
-        product.price,
-        product.id
-    )
-    .execute(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    let before = Placement::load(&app_state, &[product.id], &[]).await?;
+    app_state
+        .db
+        .update_product(&product)
+        .await
+        .map_err(internal_error)?;
+    let tags = cache_tags(&bulk::Operation::UpdateProduct(product), &before);
+    cache_invalidate(&tags, &app_state).await;
     Ok((StatusCode::OK, ()))
 }
+// Where updated rows were before the write: the category of each product and
+// the parent of each category, by id
+#[derive(Debug, Default)]
+struct Placement {
+    product_categories: HashMap<i64, Option<i64>>,
+    category_parents: HashMap<i64, Option<i64>>,
+}
+impl Placement {
+    async fn load(
+        app_state: &AppState,
+        product_ids: &[i64],
+        category_ids: &[i64],
+    ) -> Result<Self, (StatusCode, String)> {
+        let mut placement = Placement::default();
+        if !product_ids.is_empty() {
+            let products = app_state
+                .db
+                .products_in(product_ids)
+                .await
+                .map_err(internal_error)?;
+            placement.product_categories = products
+                .into_iter()
+                .map(|product| (product.id, product.category_id))
+                .collect();
+        }
+        if !category_ids.is_empty() {
+            let categories = app_state
+                .db
+                .categories_in(category_ids)
+                .await
+                .map_err(internal_error)?;
+            placement.category_parents = categories
+                .into_iter()
+                .map(|category| (category.id, category.parent_id))
+                .collect();
+        }
+        Ok(placement)
+    }
+    // of the rows `operations` update
+    async fn of(
+        app_state: &AppState,
+        operations: &[bulk::Operation],
+    ) -> Result<Self, (StatusCode, String)> {
+        let mut product_ids = Vec::new();
+        let mut category_ids = Vec::new();
+        for operation in operations {
+            match operation {
+                bulk::Operation::UpdateProduct(product) => product_ids.push(product.id),
+                bulk::Operation::UpdateCategory(category) => category_ids.push(category.id),
+                _ => {}
+            }
+        }
+        Self::load(app_state, &product_ids, &category_ids).await
+    }
+}
+// The parent of the categories operations point to and of all their ancestors,
+// enough to follow the chain up from any of them
+#[derive(Debug, Default)]
+struct CategoryParents(HashMap<i64, Option<i64>>);
+impl CategoryParents {
+    async fn load(app_state: &AppState, ids: &[i64]) -> Result<Self, (StatusCode, String)> {
+        if ids.is_empty() {
+            return Ok(Self::default());
+        }
+        let db = &app_state.db;
+        let categories = db.categories_in(ids).await.map_err(internal_error)?;
+        let ancestors = db
+            .ancestor_categories_in(ids)
+            .await
+            .map_err(internal_error)?;
+        Ok(Self(
+            categories
+                .into_iter()
+                .chain(ancestors.into_iter().map(|(_, ancestor)| ancestor))
+                .map(|category| (category.id, category.parent_id))
+                .collect(),
+        ))
+    }
+    fn contains(&self, id: i64) -> bool {
+        self.0.contains_key(&id)
+    }
+    // Whether putting category `id` under `parent_id` makes it its own ancestor
+    fn creates_cycle(&self, id: i64, parent_id: Option<i64>) -> bool {
+        let mut ancestor = parent_id;
+        // bounded in case the stored tree already loops
+        for _ in 0..=self.0.len() {
+            match ancestor {
+                Some(ancestor_id) if ancestor_id == id => return true,
+                Some(ancestor_id) => ancestor = self.0.get(&ancestor_id).copied().flatten(),
+                None => return false,
+            }
+        }
+        true
+    }
+    // Records a move, so the operations after it are checked against the tree it leaves.
+    // Categories not followed can't end up in a chain that is.
+    fn set_parent(&mut self, id: i64, parent_id: Option<i64>) {
+        if let Some(parent) = self.0.get_mut(&id) {
+            *parent = parent_id;
+        }
+    }
+}
+fn cycle_error(id: i64) -> String {
+    format!("category {id} can't be put under itself or one of its subcategories")
+}
+// Cached responses a catalogue write makes stale, for the single-item routes
+// and `/admin/bulk` alike. `before` places the rows updated by the write.
+fn cache_tags(operation: &bulk::Operation, before: &Placement) -> Vec<CacheTag> {
+    // a category's page, or the category tree for the top level
+    let parent_tag = |parent_id: Option<i64>| match parent_id {
+        Some(parent_id) => CacheTag::Category(parent_id),
+        None => CacheTag::CategoryTree,
+    };
+    match operation {
+        // a new top-level category joins the category tree, otherwise its parent's pages
+        bulk::Operation::CreateCategory(category) => vec![parent_tag(category.parent_id)],
+        // pages of the old and new parent's ancestry lose and gain this category's
+        // subtree, and a category moved from or to the top level changes the tree
+        bulk::Operation::UpdateCategory(category) => {
+            let mut tags = vec![
+                CacheTag::Category(category.id),
+                parent_tag(category.parent_id),
+            ];
+            if let Some(old_parent) = before.category_parents.get(&category.id) {
+                tags.push(parent_tag(*old_parent));
+            }
+            tags
+        }
+        bulk::Operation::DeleteCategory(category) => {
+            vec![CacheTag::Category(category.category_id)]
+        }
+        // the pages of its category and of that category's ancestry list it too
+        bulk::Operation::CreateProduct(product) => {
+            let mut tags = vec![CacheTag::Search, CacheTag::ProductPages];
+            tags.extend(product.category_id.map(CacheTag::Category));
+            tags
+        }
+        // a renamed product may now match searches it wasn't part of, and a
+        // moved one leaves its old category's pages for the new one's
+        bulk::Operation::UpdateProduct(product) => {
+            let mut tags = vec![CacheTag::Product(product.id), CacheTag::Search];
+            tags.extend(product.category_id.map(CacheTag::Category));
+            if let Some(Some(old_category)) = before.product_categories.get(&product.id) {
+                tags.push(CacheTag::Category(*old_category));
+            }
+            tags
+        }
+        // later products shift into the pages and search results it was listed on
+        bulk::Operation::DeleteProduct(product) => vec![
+            CacheTag::Product(product.product_id),
+            CacheTag::Search,
+            CacheTag::ProductPages,
+        ],
+    }
+}
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    // one transaction, nothing is applied unless everything is
+    #[default]
+    Atomic,
+    // every valid operation is applied on its own, failures are reported
+    BestEffort,
+}
+#[derive(Deserialize, ToSchema)]
+pub struct BulkRequest {
+    #[serde(default)]
+    mode: BulkMode,
+    // run in order, at most `catalogue.max_bulk_operations`
+    operations: Vec<bulk::Operation>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkStatus {
+    Applied,
+    Failed,
+    // applied, then undone when a later operation of an atomic request failed
+    RolledBack,
+    // not run because another operation of an atomic request failed
+    Skipped,
+}
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BulkResult {
+    // position of the operation in the request
+    index: usize,
+    status: BulkStatus,
+    // id of the created row
+    id: Option<i64>,
+    error: Option<String>,
+}
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct BulkReport {
+    mode: BulkMode,
+    applied: usize,
+    failed: usize,
+    // one per operation, in the order of the request
+    results: Vec<BulkResult>,
+}
+impl BulkReport {
+    fn new(mode: BulkMode, results: Vec<BulkResult>) -> Self {
+        let count = |status| {
+            results
+                .iter()
+                .filter(|result| result.status == status)
+                .count()
+        };
+        Self {
+            mode,
+            applied: count(BulkStatus::Applied),
+            failed: count(BulkStatus::Failed),
+            results,
+        }
+    }
+}
+// POST /admin/bulk { mode, operations[] } -> 200 { mode, applied, failed, results[] }, 400, 401
+#[utoipa::path(
+    post,
+    path = "/bulk",
+    tag = "admin",
+    request_body = BulkRequest,
+    responses(
+        (status = 200, body = BulkReport, description = "Applied, in best effort mode possibly in part"),
+        (status = 400, body = BulkReport, description = "Nothing was applied: an atomic request had an invalid or failing operation"),
+    )
+)]
+pub async fn bulk(
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<BulkRequest>,
+) -> HandlerResult<Json<BulkReport>> {
+    let BulkRequest { mode, operations } = request;
+    let max_operations = app_state.config.catalogue.max_bulk_operations;
+    if operations.is_empty() || operations.len() > max_operations {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("a bulk request takes between 1 and {max_operations} operations"),
+        ));
+    }
+    let count = operations.len();
+    let mut results: Vec<BulkResult> = (0..count)
+        .map(|index| BulkResult {
+            index,
+            status: BulkStatus::Skipped,
+            id: None,
+            error: None,
+        })
+        .collect();
+    // the categories the operations point to, fetched at once
+    let mut referenced: Vec<i64> = operations.iter().filter_map(category_ref).collect();
+    referenced.sort_unstable();
+    referenced.dedup();
+    let mut categories = CategoryParents::load(&app_state, &referenced).await?;
+    let mut valid = Vec::with_capacity(count);
+    for (index, operation) in operations.into_iter().enumerate() {
+        match validate(&operation, &categories) {
+            Ok(()) => {
+                if let bulk::Operation::UpdateCategory(category) = &operation {
+                    categories.set_parent(category.id, category.parent_id);
+                }
+                valid.push((index, operation));
+            }
+            Err(err) => {
+                results[index].status = BulkStatus::Failed;
+                results[index].error = Some(err);
+            }
+        }
+    }
+    if mode == BulkMode::Atomic && valid.len() < count {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(BulkReport::new(mode, results)),
+        ));
+    }
+    let (indexes, operations): (Vec<usize>, Vec<bulk::Operation>) = valid.into_iter().unzip();
+    let before = Placement::of(&app_state, &operations).await?;
+    let outcomes = match mode {
+        BulkMode::Atomic => match app_state.db.apply_all(&operations).await {
+            Ok(ids) => ids.into_iter().map(Ok).collect(),
+            Err((failed, err)) => {
+                // a failed commit or connection isn't the fault of an operation
+                let Some(message) = operations
+                    .get(failed)
+                    .and_then(|operation| operation_error(operation, &err))
+                else {
+                    return Err(internal_error(err));
+                };
+                for result in &mut results[..failed] {
+                    result.status = BulkStatus::RolledBack;
+                }
+                results[failed].status = BulkStatus::Failed;
+                results[failed].error = Some(message);
+                info!(
+                    "bulk request of {} operations rolled back at operation {}",
+                    count, failed
+                );
+                return Ok((
+                    StatusCode::BAD_REQUEST,
+                    Json(BulkReport::new(mode, results)),
+                ));
+            }
+        },
+        BulkMode::BestEffort => app_state.db.apply_each(&operations).await,
+    };
+    let mut tags = Vec::new();
+    for ((index, operation), outcome) in indexes.into_iter().zip(&operations).zip(outcomes) {
+        let result = &mut results[index];
+        match outcome {
+            Ok(id) => {
+                result.status = BulkStatus::Applied;
+                result.id = id;
+                tags.extend(cache_tags(operation, &before));
+            }
+            Err(err) => {
+                result.status = BulkStatus::Failed;
+                result.error =
+                    Some(operation_error(operation, &err).unwrap_or_else(|| err.to_string()));
+            }
+        }
+    }
+    tags.sort_by_key(|tag| tag.key());
+    tags.dedup();
+    cache_invalidate(&tags, &app_state).await;
+    // a warm-up already running may have recomputed some responses before they
+    // were dropped, those are left to the next visitor
+    if !tags.is_empty() && app_state.config.cache.warm.after_writes {
+        warm::start(&app_state, WarmTrigger::Bulk);
+    }
+    let report = BulkReport::new(mode, results);
+    info!(
+        "bulk request of {} operations: {} applied, {} failed",
+        count, report.applied, report.failed
+    );
+    Ok((StatusCode::OK, Json(report)))
+}
+// The category a product is put in or a category is put under
+fn category_ref(operation: &bulk::Operation) -> Option<i64> {
+    match operation {
+        bulk::Operation::CreateProduct(product) => product.category_id,
+        bulk::Operation::UpdateProduct(product) => product.category_id,
+        bulk::Operation::CreateCategory(category) => category.parent_id,
+        bulk::Operation::UpdateCategory(category) => category.parent_id,
+        bulk::Operation::DeleteProduct(_) | bulk::Operation::DeleteCategory(_) => None,
+    }
+}
+// Rejects what the database would store but the catalogue can't show: references
+// to categories missing from `categories` and moves making the category tree loop
+fn validate(operation: &bulk::Operation, categories: &CategoryParents) -> Result<(), String> {
+    if let Some(category_id) = category_ref(operation) {
+        if !categories.contains(category_id) {
+            return Err(format!("category {category_id} not found"));
+        }
+    }
+    let (name, price) = match operation {
+        bulk::Operation::CreateProduct(product) => (&product.name, Some(product.price)),
+        bulk::Operation::UpdateProduct(product) => (&product.name, Some(product.price)),
+        bulk::Operation::CreateCategory(category) => (&category.name, None),
+        bulk::Operation::UpdateCategory(category) => {
+            if categories.creates_cycle(category.id, category.parent_id) {
+                return Err(cycle_error(category.id));
+            }
+            (&category.name, None)
+        }
+        bulk::Operation::DeleteProduct(_) | bulk::Operation::DeleteCategory(_) => return Ok(()),
+    };
+    if name.trim().is_empty() {
+        return Err("name can't be empty".to_string());
+    }
+    if price.is_some_and(|price| price < 0) {
+        return Err("price can't be negative".to_string());
+    }
+    Ok(())
+}
+// What an operation did wrong, None when the database itself failed
+fn operation_error(operation: &bulk::Operation, err: &sqlx::Error) -> Option<String> {
+    match err {
+        sqlx::Error::RowNotFound => Some(match operation {
+            bulk::Operation::UpdateProduct(product) => format!("product {} not found", product.id),
+            bulk::Operation::DeleteProduct(product) => {
+                format!("product {} not found", product.product_id)
+            }
+            bulk::Operation::UpdateCategory(category) => {
+                format!("category {} not found", category.id)
+            }
+            bulk::Operation::DeleteCategory(category) => {
+                format!("category {} not found", category.category_id)
+            }
+            bulk::Operation::CreateProduct(_) | bulk::Operation::CreateCategory(_) => {
+                "not found".to_string()
+            }
+        }),
+        // constraint violations, e.g. a parent or category that doesn't exist
+        sqlx::Error::Database(err) => Some(err.message().to_string()),
+        _ => None,
+    }
+}
+// Normalises and validates the terms of a synonym group
+fn synonym_terms(terms: &[String]) -> Result<Vec<String>, (StatusCode, String)> {
+    let mut normalised: Vec<String> = Vec::new();
+    for term in terms.iter().map(|term| normalise_term(term)) {
+        if term.is_empty() || term.contains(char::is_whitespace) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Invalid synonym term: {term:?}"),
+            ));
+        }
+        if !normalised.contains(&term) {
+            normalised.push(term);
+        }
+    }
+    if normalised.len() < 2 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A synonym group needs at least two terms".to_string(),
+        ));
+    }
+    Ok(normalised)
+}
+// Rejects terms that already belong to a synonym group other than `group_id`
+async fn check_synonym_conflicts(
+    app_state: &Arc<AppState>,
+    terms: &[String],
+    group_id: Option<i64>,
+) -> Result<(), (StatusCode, String)> {
+    let conflicts: Vec<String> = app_state
+        .db
+        .synonyms_in(terms)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .filter(|synonym| Some(synonym.group_id) != group_id)
+        .map(|synonym| synonym.term)
+        .collect();
+    if !conflicts.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Terms already belong to another synonym group: {}",
+                conflicts.join(", ")
+            ),
+        ));
+    }
+    Ok(())
+}
+async fn group_terms(
+    app_state: &Arc<AppState>,
+    group_id: i64,
+) -> Result<Vec<String>, (StatusCode, String)> {
+    app_state
+        .db
+        .group_terms(group_id)
+        .await
+        .map_err(internal_error)
+}
+// GET /admin/search/synonyms -> 200 { synonym_group[] }, 401
+#[utoipa::path(
+    get,
+    path = "/search/synonyms",
+    tag = "admin",
+    responses(
+        (status = 200, body = Vec<SynonymGroup>),
+    )
+)]
+pub async fn synonyms_get(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<Vec<SynonymGroup>>> {
+    let synonyms = app_state.db.synonyms().await.map_err(internal_error)?;
+    let mut groups: Vec<SynonymGroup> = Vec::new();
+    for synonym in synonyms {
+        match groups.last_mut() {
+            Some(group) if group.id == synonym.group_id => group.terms.push(synonym.term),
+            _ => groups.push(SynonymGroup {
+                id: synonym.group_id,
+                terms: vec![synonym.term],
+            }),
+        }
+    }
+    Ok((StatusCode::OK, Json(groups)))
+}
+// POST /admin/search/synonyms { terms } -> 200, 400, 401
+#[utoipa::path(
+    post,
+    path = "/search/synonyms",
+    tag = "admin",
+    request_body = request::create::SynonymGroup,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+pub async fn create_synonym_group(
+    State(app_state): State<Arc<AppState>>,
+    Json(group): Json<request::create::SynonymGroup>,
+) -> HandlerResult<()> {
+    let terms = synonym_terms(&group.terms)?;
+    check_synonym_conflicts(&app_state, &terms, None).await?;
+    app_state
+        .db
+        .create_synonym_group(&terms)
+        .await
+        .map_err(internal_error)?;
+    cache_invalidate_search(&terms.into_iter().collect(), &app_state).await;
+    Ok((StatusCode::OK, ()))
+}
+// PATCH /admin/search/synonyms { synonym_group } -> 200, 400, 401, 404
+#[utoipa::path(
+    patch,
+    path = "/search/synonyms",
+    tag = "admin",
+    request_body = SynonymGroup,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+        (status = 404, description = "Synonym group not found"),
+    )
+)]
+pub async fn update_synonym_group(
+    State(app_state): State<Arc<AppState>>,
+    Json(group): Json<SynonymGroup>,
+) -> HandlerResult<()> {
+    let terms = synonym_terms(&group.terms)?;
+    let old_terms = group_terms(&app_state, group.id).await?;
+    if old_terms.is_empty() {
+        return Err((StatusCode::NOT_FOUND, "Synonym group not found".to_string()));
+    }
+    check_synonym_conflicts(&app_state, &terms, Some(group.id)).await?;
+    app_state
+        .db
+        .replace_synonym_group(group.id, &terms)
+        .await
+        .map_err(internal_error)?;
+    let affected: HashSet<String> = terms.into_iter().chain(old_terms).collect();
+    cache_invalidate_search(&affected, &app_state).await;
+    Ok((StatusCode::OK, ()))
+}
+// DELETE /admin/search/synonyms { group_id } -> 200, 400, 401
+#[utoipa::path(
+    delete,
+    path = "/search/synonyms",
+    tag = "admin",
+    request_body = request::delete::SynonymGroup,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+pub async fn delete_synonym_group(
+    State(app_state): State<Arc<AppState>>,
+    Json(group): Json<request::delete::SynonymGroup>,
+) -> HandlerResult<()> {
+    let old_terms = group_terms(&app_state, group.group_id).await?;
+    app_state
+        .db
+        .delete_synonym_group(group.group_id)
+        .await
+        .map_err(internal_error)?;
+    cache_invalidate_search(&old_terms.into_iter().collect(), &app_state).await;
+    Ok((StatusCode::OK, ()))
+}
+// GET /admin/search/stop-words -> 200 { word[] }, 401
+#[utoipa::path(
+    get,
+    path = "/search/stop-words",
+    tag = "admin",
+    responses(
+        (status = 200, body = Vec<String>),
+    )
+)]
+pub async fn stop_words_get(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<Vec<String>>> {
+    let words = app_state.db.stop_words().await.map_err(internal_error)?;
+    Ok((StatusCode::OK, Json(words)))
+}
+// POST /admin/search/stop-words { word } -> 200, 400, 401
+#[utoipa::path(
+    post,
+    path = "/search/stop-words",
+    tag = "admin",
+    request_body = request::create::StopWord,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+pub async fn create_stop_word(
+    State(app_state): State<Arc<AppState>>,
+    Json(stop_word): Json<request::create::StopWord>,
+) -> HandlerResult<()> {
+    let word = normalise_term(&stop_word.word);
+    if word.is_empty() || word.contains(char::is_whitespace) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Invalid stop word: {word:?}"),
+        ));
+    }
+    app_state
+        .db
+        .create_stop_word(&word)
+        .await
+        .map_err(internal_error)?;
+    cache_invalidate_search(&HashSet::from([word]), &app_state).await;
+    Ok((StatusCode::OK, ()))
+}
+// DELETE /admin/search/stop-words { word } -> 200, 400, 401
+#[utoipa::path(
+    delete,
+    path = "/search/stop-words",
+    tag = "admin",
+    request_body = request::delete::StopWord,
+    responses(
+        (status = 200),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+pub async fn delete_stop_word(
+    State(app_state): State<Arc<AppState>>,
+    Json(stop_word): Json<request::delete::StopWord>,
+) -> HandlerResult<()> {
+    let word = normalise_term(&stop_word.word);
+    app_state
+        .db
+        .delete_stop_word(&word)
+        .await
+        .map_err(internal_error)?;
+    cache_invalidate_search(&HashSet::from([word]), &app_state).await;
+    Ok((StatusCode::OK, ()))
+}
+#[derive(Serialize, ToSchema)]
+pub struct PurgeResponse {
+    deleted_keys: usize,
+    // whether a warm-up refilling the cache started
+    warming: bool,
+}
+// POST /admin/cache/purge -> 200 { deleted_keys, warming }, 401, 503
+#[utoipa::path(
+    post,
+    path = "/cache/purge",
+    tag = "admin",
+    responses(
+        (status = 200, body = PurgeResponse),
+        (status = 503, description = "The cache is unreachable"),
+    )
+)]
+pub async fn purge_cache(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<PurgeResponse>> {
+    let deleted_keys = app_state
+        .cache
+        .purge()
+        .await
+        .map_err(|err| (StatusCode::SERVICE_UNAVAILABLE, format!("{err:#}")))?;
+    let warming =
+        app_state.config.cache.warm.after_writes && warm::start(&app_state, WarmTrigger::Purge);
+    Ok((
+        StatusCode::OK,
+        Json(PurgeResponse {
+            deleted_keys,
+            warming,
+        }),
+    ))
+}
+// POST /admin/cache/warm -> 202 { warm_progress }, 401, 409
+#[utoipa::path(
+    post,
+    path = "/cache/warm",
+    tag = "admin",
+    responses(
+        (status = 202, body = WarmProgress),
+        (status = 409, description = "Cache warming is already running"),
+    )
+)]
+pub async fn warm_cache(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<WarmProgress>> {
+    if !warm::start(&app_state, WarmTrigger::Admin) {
+        return Err((
+            StatusCode::CONFLICT,
+            "Cache warming is already running".to_string(),
+        ));
+    }
+    Ok((StatusCode::ACCEPTED, Json(app_state.warmer.progress())))
+}
+// GET /admin/cache/warm -> 200 { warm_progress }, 401
+#[utoipa::path(
+    get,
+    path = "/cache/warm",
+    tag = "admin",
+    responses(
+        (status = 200, body = WarmProgress),
+    )
+)]
+pub async fn warm_cache_progress(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<WarmProgress>> {
+    Ok((StatusCode::OK, Json(app_state.warmer.progress())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::config::Config;
+
+    // Categories 1 and 2, product 1 in category 1
+    async fn state() -> Arc<AppState> {
+        let mut config = Config::default();
+        // the tests look at what writes leave in the cache
+        config.cache.warm.after_writes = false;
+        state_with(config).await
+    }
+
+    async fn state_with(config: Config) -> Arc<AppState> {
+        let state = AppState::for_tests(config).await;
+        for name in ["Shirts", "Shoes"] {
+            let category = request::create::Category {
+                name: name.to_string(),
+                description: None,
+                parent_id: None,
+            };
+            state.db.create_category(&category).await.unwrap();
+        }
+        let product = request::create::Product {
+            name: "Tee".to_string(),
+            description: None,
+            price: 10,
+            category_id: Some(1),
+        };
+        state.db.create_product(&product).await.unwrap();
+        Arc::new(state)
+    }
+
+    async fn run(state: &Arc<AppState>, request: Value) -> (StatusCode, BulkReport) {
+        let request = serde_json::from_value(request).unwrap();
+        let (status, Json(report)) = bulk(State(state.clone()), Json(request)).await.unwrap();
+        (status, report)
+    }
+
+    fn statuses(report: &BulkReport) -> Vec<BulkStatus> {
+        report.results.iter().map(|result| result.status).collect()
+    }
+
+    async fn product_count(state: &AppState) -> i64 {
+        state.db.catalogue_counts().await.unwrap().0
+    }
+
+    fn operations() -> Value {
+        json!([
+            { "op": "create_product", "name": "Socks", "description": null, "price": 5, "category_id": 2 },
+            { "op": "update_product", "id": 1, "name": "Tee", "description": null, "price": 12, "category_id": 2 },
+            { "op": "delete_product", "product_id": 99 },
+        ])
+    }
+
+    #[tokio::test]
+    async fn atomic_requests_roll_back_on_a_failing_operation() {
+        let state = state().await;
+        let (status, report) = run(&state, json!({ "operations": operations() })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            statuses(&report),
+            [
+                BulkStatus::RolledBack,
+                BulkStatus::RolledBack,
+                BulkStatus::Failed
+            ]
+        );
+        assert_eq!((report.applied, report.failed), (0, 1));
+        assert_eq!(
+            report.results[2].error.as_deref(),
+            Some("product 99 not found")
+        );
+        assert_eq!(product_count(&state).await, 1);
+        let tee = state.db.product(1).await.unwrap().unwrap();
+        assert_eq!((tee.price, tee.category_id), (10, Some(1)));
+    }
+
+    #[tokio::test]
+    async fn best_effort_requests_apply_what_they_can() {
+        let state = state().await;
+        let request = json!({ "mode": "best_effort", "operations": operations() });
+        let (status, report) = run(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            statuses(&report),
+            [BulkStatus::Applied, BulkStatus::Applied, BulkStatus::Failed]
+        );
+        assert_eq!((report.applied, report.failed), (2, 1));
+        assert_eq!(report.results[0].id, Some(2));
+        assert_eq!(product_count(&state).await, 2);
+        let tee = state.db.product(1).await.unwrap().unwrap();
+        assert_eq!((tee.price, tee.category_id), (12, Some(2)));
+    }
+
+    // Invalid operations fail before anything runs, atomic requests skip the rest
+    #[tokio::test]
+    async fn invalid_operations_are_not_run() {
+        let operations = json!([
+            { "op": "create_product", "name": "Socks", "description": null, "price": 5, "category_id": 2 },
+            { "op": "update_product", "id": 1, "name": "Tee", "description": null, "price": 12, "category_id": 7 },
+        ]);
+        let state = state().await;
+        let (status, report) = run(&state, json!({ "operations": operations })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(statuses(&report), [BulkStatus::Skipped, BulkStatus::Failed]);
+        assert_eq!(
+            report.results[1].error.as_deref(),
+            Some("category 7 not found")
+        );
+        assert_eq!(product_count(&state).await, 1);
+
+        let request = json!({ "mode": "best_effort", "operations": operations });
+        let (status, report) = run(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(statuses(&report), [BulkStatus::Applied, BulkStatus::Failed]);
+        assert_eq!(product_count(&state).await, 2);
+    }
+
+    // Shirts (1) > Tees (3) > Plain (4), and Shoes (2)
+    async fn nested_state() -> Arc<AppState> {
+        let state = state().await;
+        for (name, parent_id) in [("Tees", 1), ("Plain", 3)] {
+            let category = request::create::Category {
+                name: name.to_string(),
+                description: None,
+                parent_id: Some(parent_id),
+            };
+            state.db.create_category(&category).await.unwrap();
+        }
+        state
+    }
+
+    async fn parent_of(state: &AppState, id: i64) -> Option<i64> {
+        state.db.categories_in(&[id]).await.unwrap()[0].parent_id
+    }
+
+    fn move_category(id: i64, parent_id: i64) -> Value {
+        json!({ "op": "update_category", "id": id, "name": "Moved", "description": null, "parent_id": parent_id })
+    }
+
+    #[tokio::test]
+    async fn category_cycles_are_refused() {
+        let state = nested_state().await;
+        // under a grandchild, after a valid operation
+        let request = json!({ "operations": [
+            { "op": "update_product", "id": 1, "name": "Tee", "description": null, "price": 12, "category_id": 2 },
+            move_category(1, 4),
+        ] });
+        let (status, report) = run(&state, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(statuses(&report), [BulkStatus::Skipped, BulkStatus::Failed]);
+        assert_eq!(
+            report.results[1].error.as_deref(),
+            Some(cycle_error(1).as_str())
+        );
+        assert_eq!(parent_of(&state, 1).await, None);
+        assert_eq!(state.db.product(1).await.unwrap().unwrap().price, 10);
+
+        // two moves that only loop together
+        let request = json!({ "operations": [move_category(2, 4), move_category(1, 2)] });
+        let (status, report) = run(&state, request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(statuses(&report), [BulkStatus::Skipped, BulkStatus::Failed]);
+        assert_eq!(parent_of(&state, 2).await, None);
+
+        // moving down a branch that isn't the category's own is fine
+        let request = json!({ "operations": [move_category(2, 4)] });
+        let (status, _) = run(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(parent_of(&state, 2).await, Some(4));
+
+        for parent_id in [1, 4] {
+            let category = Category {
+                id: 1,
+                name: "Shirts".to_string(),
+                description: None,
+                parent_id: Some(parent_id),
+            };
+            let (status, _) = update_category(State(state.clone()), Json(category))
+                .await
+                .err()
+                .unwrap();
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(parent_of(&state, 1).await, None);
+    }
+
+    #[tokio::test]
+    async fn empty_requests_are_refused() {
+        let request = serde_json::from_value(json!({ "operations": [] })).unwrap();
+        let (status, _) = bulk(State(state().await), Json(request))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    // Moving a product drops the cached pages of both its categories
+    #[tokio::test]
+    async fn moves_invalidate_the_old_and_new_category() {
+        let state = state().await;
+        for category_id in [1, 2] {
+            let tags = [CacheTag::Category(category_id)];
+            let key = format!("/category?id={category_id}");
+            state
+                .cache
+                .set(&key, "{}".to_string(), Duration::from_secs(60), &tags)
+                .await
+                .unwrap();
+        }
+        let request = json!({ "operations": [
+            { "op": "update_product", "id": 1, "name": "Tee", "description": null, "price": 10, "category_id": 2 },
+        ] });
+        let (status, _) = run(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        for key in ["/category?id=1", "/category?id=2"] {
+            assert!(state.cache.get(key).await.unwrap().is_none(), "{key}");
+        }
+    }
+
+    #[test]
+    fn category_moves_tag_both_parents() {
+        let category = Category {
+            id: 3,
+            name: "Tees".to_string(),
+            description: None,
+            parent_id: Some(2),
+        };
+        let mut before = Placement::default();
+        before.category_parents.insert(3, Some(1));
+        let tags = cache_tags(&bulk::Operation::UpdateCategory(category.clone()), &before);
+        for tag in [
+            CacheTag::Category(3),
+            CacheTag::Category(2),
+            CacheTag::Category(1),
+        ] {
+            assert!(tags.contains(&tag), "{tag:?}");
+        }
+        // from the top level, the category tree changes
+        before.category_parents.insert(3, None);
+        let tags = cache_tags(&bulk::Operation::UpdateCategory(category), &before);
+        assert!(tags.contains(&CacheTag::CategoryTree));
+    }
+
+    #[tokio::test]
+    async fn purges_and_bulk_writes_refill_the_cache() {
+        let state = state_with(Config::default()).await;
+        let warmed = async |state: &Arc<AppState>| {
+            // waits for the warm-up
+            state.tasks.close();
+            state.tasks.wait().await;
+            state.tasks.reopen();
+            let progress = state.warmer.progress();
+            (progress.trigger, progress.total)
+        };
+        let (_, Json(purged)) = purge_cache(State(state.clone())).await.unwrap();
+        assert!(purged.warming);
+        // the tree and both top-level categories
+        assert!(matches!(
+            warmed(&state).await,
+            (Some(WarmTrigger::Purge), 3)
+        ));
+        assert!(state.cache.get("/category?id=2").await.unwrap().is_some());
+
+        let request = json!({ "operations": [
+            { "op": "update_product", "id": 1, "name": "Tee", "description": null, "price": 12, "category_id": 1 },
+        ] });
+        let (status, _) = run(&state, request).await;
+        assert_eq!(status, StatusCode::OK);
+        // the tree and category 1, which holds the product, category 2 is still cached
+        assert!(matches!(warmed(&state).await, (Some(WarmTrigger::Bulk), 2)));
+        assert!(state.cache.get("/category?id=1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn login_cookie_is_sent_to_the_whole_site() {
+        let state = state().await;
+        create_admin(&*state.db, "admin".to_string(), "secret".to_string(), 4)
+            .await
+            .unwrap();
+        let payload = LoginPayload {
+            username: "admin".to_string(),
+            password: "secret".to_string(),
+        };
+        let (status, headers) = login(State(state.clone()), Json(payload)).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        let cookie = headers[header::SET_COOKIE].to_str().unwrap();
+        let mut attributes = cookie.split("; ");
+        let token = attributes.next().unwrap();
+        assert!(token.starts_with(&format!("{AUTH_COOKIE}=")), "{cookie}");
+        let attributes: Vec<_> = attributes.collect();
+        for attribute in ["Path=/", "HttpOnly", "SameSite=Lax"] {
+            assert!(attributes.contains(&attribute), "{cookie}");
+        }
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(header::COOKIE, token.parse().unwrap());
+        assert!(session(&state, &request_headers).await.unwrap().is_some());
+    }
+}
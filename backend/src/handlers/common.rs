@@ -1,21 +1,27 @@
-use std::{os::unix::process::parent_id, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
-use serde_json::Serializer;
+use serde_json::{json, Value};
 use tracing::info;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::{
-    handlers::{internal_error, redis_get, redis_set, HandlerResult},
-    models::{Category, Product, SubCategory},
+    cache::{CacheEntry, CacheKey, CacheTag},
+    expand::{ExpandParams, Expansion},
+    handlers::{cache_get_many, cache_refresh, cache_set, cached, internal_error, HandlerResult},
+    http_cache::LastModified,
+    models::{Category, Product},
+    search::{expand_query, tokenize},
     AppState,
 };
-const PAGE_SIZE: i64 = 50;
 pub async fn test(
     _app_state: State<Arc<AppState>>,
     Path(test): Path<String>,
@@ -23,206 +29,585 @@ pub async fn test(
     info!("Received test param: {}", test);
     Ok((StatusCode::OK, Json(())))
 }
-// GET /products/:page -> 200 { product[] }, 404
+// GET /products/:page -> 200 { product[] }, 400, 404
+#[utoipa::path(
+    get,
+    path = "/products/{page}",
+    tag = "catalogue",
+    params(
+        ("page" = i64, Path, description = "Zero-based page of `catalogue.page_size` products"),
+        ExpandParams,
+    ),
+    responses(
+        (status = 200, body = Vec<Product>),
+        (status = 304, description = "Unchanged since the `If-None-Match` / `If-Modified-Since` of the request"),
+        (status = 400, description = "Negative `page`, or unknown `include` or `fields`"),
+    )
+)]
 pub async fn product_page(
     app_state: State<Arc<AppState>>,
     Path(page): Path<i64>,
-) -> HandlerResult<Json<Vec<Product>>> {
-    let offset = PAGE_SIZE * page;
-    let products = sqlx::query_as!(
-        Product,
-        r#"
-        SELECT
-            id,
-            name,
-            description,
-            price,
-            category_id
-        FROM products
-        ORDER BY id
-        LIMIT ? OFFSET ?
-        "#,
-        PAGE_SIZE,
-        offset
-    )
-    .fetch_all(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
-    Ok((StatusCode::OK, Json(products)))
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<(LastModified, Json<Vec<Value>>)> {
+    let expansion = Expansion::try_from(expand)?;
+    let products = cached_product_page(&app_state, page).await?;
+    Ok((
+        StatusCode::OK,
+        (
+            LastModified::from_unix(products.stored_at),
+            Json(expansion.products(products.value, &app_state).await?),
+        ),
+    ))
+}
+// The page of products every version of `/products/:page` is built from
+pub async fn cached_product_page(
+    app_state: &Arc<AppState>,
+    page: i64,
+) -> Result<CacheEntry<Vec<Product>>, (StatusCode, String)> {
+    let page_size = app_state.config.catalogue.page_size;
+    let offset = page_offset(page, page_size)?;
+    let ttl = app_state.config.cache.ttl.products;
+    let load = load_product_page(app_state.clone(), offset);
+    cached(product_page_key(page, page_size), ttl, load, app_state).await
+}
+// Offset of the first row of a page, 400 for a negative page or one past the last possible row
+fn page_offset(page: i64, page_size: i64) -> Result<i64, (StatusCode, String)> {
+    if page < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "page must not be negative".to_string(),
+        ));
+    }
+    page_size.checked_mul(page).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("page {page} is out of range"),
+        )
+    })
 }
-// GET /product/search/:query/:page -> 200 { product[] }, 404
+pub fn product_page_key(page: i64, page_size: i64) -> String {
+    CacheKey::new("/products")
+        .param("page", page)
+        .param("page_size", page_size)
+        .build()
+}
+async fn load_product_page(
+    app_state: Arc<AppState>,
+    offset: i64,
+) -> Result<(Vec<Product>, Vec<CacheTag>), (StatusCode, String)> {
+    let page_size = app_state.config.catalogue.page_size;
+    let products = app_state
+        .db
+        .product_page(page_size, offset)
+        .await
+        .map_err(internal_error)?;
+    let tags = products
+        .iter()
+        .map(|product| CacheTag::Product(product.id))
+        .chain([CacheTag::ProductPages])
+        .collect();
+    Ok((products, tags))
+}
+// GET /product/search/:query/:page -> 200 { product[] }, 400, 404
+#[utoipa::path(
+    get,
+    path = "/product/search/{query}/{page}",
+    tag = "catalogue",
+    params(
+        ("query" = String, Path, description = "Search terms, expanded with synonyms"),
+        ("page" = i64, Path, description = "Zero-based page of results"),
+        ExpandParams,
+    ),
+    responses(
+        (status = 200, body = Vec<Product>),
+        (status = 304, description = "Unchanged since the `If-None-Match` / `If-Modified-Since` of the request"),
+        (status = 400, description = "Negative `page`, or unknown `include` or `fields`"),
+    )
+)]
 pub async fn product_search(
     app_state: State<Arc<AppState>>,
     Path((query, page)): Path<(String, i64)>,
-) -> HandlerResult<Json<Vec<Product>>> {
-    let redis_key = format!("/product/search/{}", &query);
-    let cached: Option<Vec<Product>> = redis_get(&redis_key, &app_state).await?;
-    if let Some(cached) = cached {
-        info!("Using redis cached response!!");
-        return Ok((StatusCode::OK, Json(cached)));
-    }
-    let offset = PAGE_SIZE * page;
-    let products = sqlx::query_as!(
-        Product,
-        r#"
-    SELECT
-        id,
-        name,
-        description,
-        price,
-        category_id
-    FROM products
-    WHERE name LIKE '%' || ? || '%'
-    ORDER BY id
-    LIMIT ? OFFSET ?
-    "#,
-        query,
-        PAGE_SIZE,
-        offset
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<(LastModified, Json<Vec<Value>>)> {
+    let expansion = Expansion::try_from(expand)?;
+    let products = cached_search(&app_state, &query, page).await?;
+    Ok((
+        StatusCode::OK,
+        (
+            LastModified::from_unix(products.stored_at),
+            Json(expansion.products(products.value, &app_state).await?),
+        ),
+    ))
+}
+// A page of search results, cached per normalised query
+pub async fn cached_search(
+    app_state: &Arc<AppState>,
+    query: &str,
+    page: i64,
+) -> Result<CacheEntry<Vec<Product>>, (StatusCode, String)> {
+    let query = tokenize(query).join(" ");
+    let page_size = app_state.config.catalogue.page_size;
+    let offset = page_offset(page, page_size)?;
+    let cache_key = CacheKey::new("/product/search")
+        .text("query", &query)
+        .param("page", page)
+        .param("page_size", page_size)
+        .build();
+    let ttl = app_state.config.cache.ttl.search;
+    let load = search_products(app_state.clone(), query, offset);
+    cached(cache_key, ttl, load, app_state).await
+}
+async fn search_products(
+    app_state: Arc<AppState>,
+    query: String,
+    offset: i64,
+) -> Result<(Vec<Product>, Vec<CacheTag>), (StatusCode, String)> {
+    let page_size = app_state.config.catalogue.page_size;
+    let term_groups = expand_query(app_state.db.as_ref(), &query)
+        .await
+        .map_err(internal_error)?;
+    let products = app_state
+        .db
+        .search_products(&term_groups, page_size, offset)
+        .await
+        .map_err(internal_error)?;
+    let tags: Vec<CacheTag> = products
+        .iter()
+        .map(|product| CacheTag::Product(product.id))
+        .chain([CacheTag::Search])
+        .collect();
+    Ok((products, tags))
+}
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ProductIdsQuery {
+    /// Comma separated product ids, at most `catalogue.max_batch_ids`
+    #[param(example = "1,2,3")]
+    ids: String,
+}
+#[derive(Deserialize, ToSchema)]
+pub struct ProductIds {
+    // at most `catalogue.max_batch_ids`
+    ids: Vec<i64>,
+}
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProductBatch {
+    // in the order of the request, without duplicates, shaped by `include` and `fields`
+    #[schema(value_type = Vec<Product>)]
+    products: Vec<Value>,
+    // requested ids without a product
+    missing: Vec<i64>,
+}
+// GET /products?ids=1,2,3 -> 200 { products: product[], missing: id[] }, 400
+#[utoipa::path(
+    get,
+    path = "/products",
+    tag = "catalogue",
+    params(ProductIdsQuery, ExpandParams),
+    responses(
+        (status = 200, body = ProductBatch),
+        (status = 400, description = "Malformed or too many `ids`, unknown `include` or `fields`"),
     )
-    .fetch_all(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
-    redis_set(redis_key, &products, &app_state).await?;
-    Ok((StatusCode::OK, Json(products)))
+)]
+pub async fn products_get(
+    app_state: State<Arc<AppState>>,
+    Query(query): Query<ProductIdsQuery>,
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<Json<ProductBatch>> {
+    let ids = query
+        .ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse().map_err(|_| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("invalid product id {id:?}"),
+                )
+            })
+        })
+        .collect::<Result<Vec<i64>, _>>()?;
+    product_batch(&app_state, ids, expand).await
+}
+// POST /products { ids: id[] } -> 200 { products: product[], missing: id[] }, 400
+// For lists too long for a query string, nothing is written
+#[utoipa::path(
+    post,
+    path = "/products",
+    tag = "catalogue",
+    params(ExpandParams),
+    request_body = ProductIds,
+    responses(
+        (status = 200, body = ProductBatch),
+        (status = 400, description = "Too many `ids`, unknown `include` or `fields`"),
+    )
+)]
+pub async fn products_post(
+    app_state: State<Arc<AppState>>,
+    Query(expand): Query<ExpandParams>,
+    Json(body): Json<ProductIds>,
+) -> HandlerResult<Json<ProductBatch>> {
+    product_batch(&app_state, body.ids, expand).await
+}
+// Products are cached one by one, so overlapping batches such as the refreshes
+// of a cart share their entries and only the misses reach the database, in one query
+async fn product_batch(
+    app_state: &Arc<AppState>,
+    mut ids: Vec<i64>,
+    expand: ExpandParams,
+) -> HandlerResult<Json<ProductBatch>> {
+    let expansion = Expansion::try_from(expand)?;
+    let mut seen = HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+    let max_ids = app_state.config.catalogue.max_batch_ids;
+    if ids.len() > max_ids {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("at most {max_ids} ids can be fetched at once"),
+        ));
+    }
+    let keys: Vec<String> = ids.iter().map(|id| product_key(*id)).collect();
+    let mut found: HashMap<i64, Product> = cache_get_many::<CacheEntry<Product>>(&keys, app_state)
+        .await
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.is_fresh())
+        .map(|entry| (entry.value.id, entry.value))
+        .collect();
+    let misses: Vec<i64> = ids
+        .iter()
+        .copied()
+        .filter(|id| !found.contains_key(id))
+        .collect();
+    if !misses.is_empty() {
+        let products = app_state
+            .db
+            .products_in(&misses)
+            .await
+            .map_err(internal_error)?;
+        let ttl = app_state.config.cache.ttl.product;
+        let expiry = ttl + app_state.config.cache.ttl.stale;
+        for product in products {
+            let entry = CacheEntry::new(&product, ttl);
+            let tags = [CacheTag::Product(product.id)];
+            cache_set(&product_key(product.id), &entry, expiry, &tags, app_state).await;
+            found.insert(product.id, product);
+        }
+    }
+    let (products, missing): (Vec<i64>, Vec<i64>) =
+        ids.into_iter().partition(|id| found.contains_key(id));
+    let products = products
+        .into_iter()
+        .filter_map(|id| found.remove(&id))
+        .collect();
+    Ok((
+        StatusCode::OK,
+        Json(ProductBatch {
+            products: expansion.products(products, app_state).await?,
+            missing,
+        }),
+    ))
+}
+fn product_key(product_id: i64) -> String {
+    CacheKey::new("/product").param("id", product_id).build()
 }
 // GET /product/:id -> 200 { product }, 404
+#[utoipa::path(
+    get,
+    path = "/product/{id}",
+    tag = "catalogue",
+    params(("id" = u32, Path), ExpandParams),
+    responses(
+        (status = 200, body = Product),
+        (status = 304, description = "Unchanged since the `If-None-Match` of the request"),
+        (status = 400, description = "Unknown `include` or `fields`"),
+        (status = 404, description = "Product not found"),
+    )
+)]
 pub async fn product_get(
     app_state: State<Arc<AppState>>,
     Path(product_id): Path<u32>,
-) -> HandlerResult<Json<Product>> {
-    let product = sqlx::query_as!(Product, "SELECT * from products WHERE id = ?", product_id)
-        .fetch_optional(&app_state.pg)
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<Json<Value>> {
+    let expansion = Expansion::try_from(expand)?;
+    let product = app_state
+        .db
+        .product(product_id.into())
         .await
         .map_err(internal_error)?;
     if let Some(product) = product {
-        Ok((StatusCode::OK, Json(product)))
+        let mut products = expansion.products(vec![product], &app_state).await?;
+        Ok((StatusCode::OK, Json(products.remove(0))))
     } else {
         Err((StatusCode::NOT_FOUND, "Product not found".to_string()))
     }
 }
 // GET /categories -> 200 { parent_categories[] }
+#[utoipa::path(
+    get,
+    path = "/categories",
+    tag = "catalogue",
+    responses(
+        (status = 200, description = "Top-level categories", body = Vec<Category>),
+        (status = 304, description = "Unchanged since the `If-None-Match` / `If-Modified-Since` of the request"),
+    )
+)]
 pub async fn parent_categories_get(
     app_state: State<Arc<AppState>>,
-) -> HandlerResult<Json<Vec<Category>>> {
-    let parent_categories =
-        sqlx::query_as!(Category, "SELECT * from categories WHERE parent_id IS NULL")
-            .fetch_all(&app_state.pg)
-            .await
-            .map_err(internal_error)?;
-    Ok((StatusCode::OK, Json(parent_categories)))
+) -> HandlerResult<(LastModified, Json<Vec<Category>>)> {
+    let parent_categories = cached_parent_categories(&app_state).await?;
+    Ok((
+        StatusCode::OK,
+        (
+            LastModified::from_unix(parent_categories.stored_at),
+            Json(parent_categories.value),
+        ),
+    ))
+}
+pub async fn cached_parent_categories(
+    app_state: &Arc<AppState>,
+) -> Result<CacheEntry<Vec<Category>>, (StatusCode, String)> {
+    let ttl = app_state.config.cache.ttl.categories;
+    let load = load_parent_categories(app_state.clone());
+    cached(parent_categories_key(), ttl, load, app_state).await
+}
+fn parent_categories_key() -> String {
+    CacheKey::new("/categories").build()
+}
+async fn load_parent_categories(
+    app_state: Arc<AppState>,
+) -> Result<(Vec<Category>, Vec<CacheTag>), (StatusCode, String)> {
+    let parent_categories = app_state
+        .db
+        .parent_categories()
+        .await
+        .map_err(internal_error)?;
+    let tags = parent_categories
+        .iter()
+        .map(|category| CacheTag::Category(category.id))
+        .chain([CacheTag::CategoryTree])
+        .collect();
+    Ok((parent_categories, tags))
 }
 
 // GET /category/:id -> 200 { product[], sub_categories[], parent_categories[] }, 404
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct GetCategoryResponse {
     products: Vec<Product>,
     sub_categories: Vec<Category>,
     parent_categories: Vec<Category>,
 }
+impl GetCategoryResponse {
+    // The page of a category changes with the category itself, any category in its
+    // subtree or ancestry and any product listed on it
+    fn cache_tags(&self, category_id: i64) -> Vec<CacheTag> {
+        let categories = self
+            .sub_categories
+            .iter()
+            .chain(&self.parent_categories)
+            .map(|category| CacheTag::Category(category.id));
+        let products = self
+            .products
+            .iter()
+            .map(|product| CacheTag::Product(product.id));
+        [CacheTag::Category(category_id)]
+            .into_iter()
+            .chain(categories)
+            .chain(products)
+            .collect()
+    }
+}
+#[utoipa::path(
+    get,
+    path = "/category/{id}",
+    tag = "catalogue",
+    params(("id" = i64, Path), ExpandParams),
+    responses(
+        (status = 200, body = GetCategoryResponse, description = "`include` and `fields` apply to `products`"),
+        (status = 304, description = "Unchanged since the `If-None-Match` / `If-Modified-Since` of the request"),
+        (status = 400, description = "Unknown `include` or `fields`"),
+    )
+)]
 pub async fn category_get(
     app_state: State<Arc<AppState>>,
     Path(category_id): Path<i64>,
-) -> HandlerResult<Json<GetCategoryResponse>> {
-    let redis_key = format!("/category/{}", &category_id);
-    let cached: Option<GetCategoryResponse> = redis_get(&redis_key, &app_state).await?;
-    if let Some(cached) = cached {
-        info!("Using redis cached response!!");
-        return Ok((StatusCode::OK, Json(cached)));
-    }
-    let products = sqlx::query_as!(
-        Product,
-        r#"
-    WITH RECURSIVE subcategories AS (
-        SELECT id
-        FROM categories
-        WHERE id = ?
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<(LastModified, Json<Value>)> {
+    let expansion = Expansion::try_from(expand)?;
+    let ttl = app_state.config.cache.ttl.category;
+    let load = load_category(app_state.0.clone(), category_id);
+    let resp = cached(category_key(category_id), ttl, load, &app_state).await?;
+    let GetCategoryResponse {
+        products,
+        sub_categories,
+        parent_categories,
+    } = resp.value;
+    let products = expansion.products(products, &app_state).await?;
+    Ok((
+        StatusCode::OK,
+        (
+            LastModified::from_unix(resp.stored_at),
+            Json(json!({
+                "products": products,
+                "sub_categories": sub_categories,
+                "parent_categories": parent_categories,
+            })),
+        ),
+    ))
+}
+pub fn category_key(category_id: i64) -> String {
+    CacheKey::new("/category").param("id", category_id).build()
+}
+// Runs the recursive category queries, the expensive part of `category_get`
+async fn load_category(
+    app_state: Arc<AppState>,
+    category_id: i64,
+) -> Result<(GetCategoryResponse, Vec<CacheTag>), (StatusCode, String)> {
+    let products = app_state
+        .db
+        .category_products(category_id)
+        .await
+        .map_err(internal_error)?;
+    let sub_categories = app_state
+        .db
+        .sub_categories(category_id)
+        .await
+        .map_err(internal_error)?;
+    let parent_categories = app_state
+        .db
+        .ancestor_categories(category_id)
+        .await
+        .map_err(internal_error)?;
+    let resp = GetCategoryResponse {
+        products,
+        sub_categories,
+        parent_categories,
+    };
+    let tags = resp.cache_tags(category_id);
+    Ok((resp, tags))
+}
+// Recomputes the cached `/categories` response, returns the top-level category ids
+pub async fn warm_parent_categories(
+    app_state: &Arc<AppState>,
+) -> Result<Vec<i64>, (StatusCode, String)> {
+    let ttl = app_state.config.cache.ttl.categories;
+    let load = load_parent_categories(app_state.clone());
+    let parent_categories: Vec<Category> =
+        cache_refresh(parent_categories_key(), ttl, load, app_state).await?;
+    Ok(parent_categories
+        .iter()
+        .map(|category| category.id)
+        .collect())
+}
+// Recomputes the cached `/category/:id` response
+pub async fn warm_category(
+    app_state: &Arc<AppState>,
+    category_id: i64,
+) -> Result<(), (StatusCode, String)> {
+    let ttl = app_state.config.cache.ttl.category;
+    let load = load_category(app_state.clone(), category_id);
+    cache_refresh::<GetCategoryResponse, _>(category_key(category_id), ttl, load, app_state)
+        .await?;
+    Ok(())
+}
+// Recomputes the cached `/products/:page` response
+pub async fn warm_product_page(
+    app_state: &Arc<AppState>,
+    page: i64,
+) -> Result<(), (StatusCode, String)> {
+    let page_size = app_state.config.catalogue.page_size;
+    let offset = page_offset(page, page_size)?;
+    let ttl = app_state.config.cache.ttl.products;
+    let load = load_product_page(app_state.clone(), offset);
+    cache_refresh::<Vec<Product>, _>(product_page_key(page, page_size), ttl, load, app_state)
+        .await?;
+    Ok(())
+}
 
-        UNION ALL
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, models::request};
 
-        SELECT c.id
-        FROM categories c
-        JOIN subcategories sc ON c.parent_id = sc.id
-    )
-    SELECT
-        p.id,
-        p.name,
-        p.description,
-        p.price,
-        p.category_id
-    FROM products p
-    JOIN subcategories sc ON p.category_id = sc.id
-    ORDER BY p.id
-    "#,
-        category_id
-    )
-    .fetch_all(&app_state.pg)
-    .await
-    .map_err(internal_error)?;
+    // Products 1 to 3, at most `max_ids` per batch
+    async fn state(max_ids: usize) -> Arc<AppState> {
+        let mut config = Config::default();
+        config.catalogue.max_batch_ids = max_ids;
+        let state = AppState::for_tests(config).await;
+        for name in ["Tee", "Socks", "Cap"] {
+            let product = request::create::Product {
+                name: name.to_string(),
+                description: None,
+                price: 10,
+                category_id: None,
+            };
+            state.db.create_product(&product).await.unwrap();
+        }
+        Arc::new(state)
+    }
 
-    let sub_categories = sqlx::query_as!(
-        SubCategory,
-        r#"
-    WITH RECURSIVE subcategories AS (
-        SELECT id, name, description, parent_id
-        FROM categories
-        WHERE parent_id = ?
+    async fn batch(state: &Arc<AppState>, ids: &[i64]) -> HandlerResult<Json<ProductBatch>> {
+        product_batch(state, ids.to_vec(), ExpandParams::default()).await
+    }
 
-        UNION ALL
+    fn ids(batch: &ProductBatch) -> Vec<i64> {
+        batch
+            .products
+            .iter()
+            .map(|product| product["id"].as_i64().unwrap())
+            .collect()
+    }
 
-        SELECT c.id, c.name, c.description, c.parent_id
-        FROM categories c
-        JOIN subcategories sc ON c.parent_id = sc.id
-    )
-    SELECT id, name, description, parent_id
-    FROM subcategories
-    ORDER BY id
-    "#,
-        category_id
-    )
-    .fetch_all(&app_state.pg)
-    .await
-    .map_err(internal_error)?
-    .iter()
-    .filter_map(|sc| sc.try_into().ok())
-    .collect();
-    let parent_categories: Vec<Category> = sqlx::query_as!(
-        SubCategory,
-        r#"
-    WITH RECURSIVE parent_categories AS (
-        SELECT id, name, description, parent_id
-        FROM categories
-        WHERE id = ?
+    #[tokio::test]
+    async fn batches_keep_the_order_asked_for_once_per_id() {
+        let state = state(10).await;
+        let (status, Json(batch)) = batch(&state, &[3, 1, 3, 99, 1, 98]).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ids(&batch), [3, 1]);
+        assert_eq!(batch.missing, [99, 98]);
+    }
 
-        UNION ALL
+    #[tokio::test]
+    async fn found_products_are_cached_one_by_one() {
+        let state = state(10).await;
+        let (_, Json(first)) = batch(&state, &[1, 99]).await.unwrap();
+        assert_eq!(first.missing, [99]);
+        assert!(state.cache.get(&product_key(1)).await.unwrap().is_some());
+        assert!(state.cache.get(&product_key(99)).await.unwrap().is_none());
+        // served from the cache while fresh
+        state.db.delete_product(1).await.unwrap();
+        let (_, Json(batch)) = batch(&state, &[1, 2]).await.unwrap();
+        assert_eq!(ids(&batch), [1, 2]);
+    }
 
-        SELECT c.id, c.name, c.description, c.parent_id
-        FROM categories c
-        JOIN parent_categories pc ON pc.parent_id = c.id
-    )
-    SELECT
-        id,
-        name,
-        description,
-        parent_id
-    FROM parent_categories
-    WHERE id != ?
-    ORDER BY id
-    "#,
-        category_id,
-        category_id
-    )
-    .fetch_all(&app_state.pg)
-    .await
-    .map_err(internal_error)?
-    .iter()
-    .filter_map(|sc| sc.try_into().ok())
-    .collect();
-    let resp = GetCategoryResponse {
-        products,
-        sub_categories,
-        parent_categories,
-    };
-    redis_set(redis_key, &resp, &app_state).await?;
-    Ok((StatusCode::OK, Json(resp)))
+    #[tokio::test]
+    async fn batches_are_limited_to_max_ids_after_deduplication() {
+        let state = state(2).await;
+        let (status, message) = batch(&state, &[1, 2, 3]).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(message.contains("at most 2 ids"), "{message}");
+        let (_, Json(batch)) = batch(&state, &[1, 2, 1, 2]).await.unwrap();
+        assert_eq!(ids(&batch), [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn query_ids_must_be_numbers() {
+        let state = state(10).await;
+        let query = |ids: &str| {
+            Query(ProductIdsQuery {
+                ids: ids.to_string(),
+            })
+        };
+        let expand = || Query(ExpandParams::default());
+        let (_, Json(batch)) = products_get(State(state.clone()), query(" 2, ,1,"), expand())
+            .await
+            .unwrap();
+        assert_eq!(ids(&batch), [2, 1]);
+        let (status, _) = products_get(State(state), query("1,two"), expand())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 }
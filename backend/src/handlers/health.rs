@@ -0,0 +1,227 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+use tokio::time::timeout;
+use utoipa::ToSchema;
+
+use crate::{config::CacheBackend, handlers::HandlerResult, AppState};
+
+// How long a dependency gets to answer a readiness check
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Up,
+    Down,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Readiness {
+    Ready,
+    // serving, but the cache is down so every request reads the database
+    Degraded,
+    NotReady,
+}
+#[derive(Serialize, ToSchema)]
+pub struct DependencyCheck {
+    status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+#[derive(Serialize, ToSchema)]
+pub struct MigrationsCheck {
+    status: CheckStatus,
+    applied: Option<i64>,
+    expected: i64,
+}
+#[derive(Serialize, ToSchema)]
+pub struct CacheCheck {
+    backend: CacheBackend,
+    status: CheckStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessChecks {
+    database: DependencyCheck,
+    migrations: MigrationsCheck,
+    cache: CacheCheck,
+}
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    status: Readiness,
+    checks: ReadinessChecks,
+}
+#[derive(Serialize, ToSchema)]
+pub struct VersionResponse {
+    version: &'static str,
+    git_sha: &'static str,
+    schema_version: Option<i64>,
+}
+
+// Runs a check, timing out rather than hanging on an unreachable dependency
+async fn check<E: std::fmt::Display>(
+    fut: impl Future<Output = Result<(), E>>,
+) -> (CheckStatus, Option<String>) {
+    match timeout(CHECK_TIMEOUT, fut).await {
+        Ok(Ok(())) => (CheckStatus::Up, None),
+        Ok(Err(err)) => (CheckStatus::Down, Some(format!("{err:#}"))),
+        Err(_) => (CheckStatus::Down, Some("timed out".to_string())),
+    }
+}
+
+// GET /healthz -> 200
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "operations",
+    responses((status = 200, description = "The process is up", body = String, content_type = "text/plain"))
+)]
+pub async fn healthz() -> HandlerResult<&'static str> {
+    Ok((StatusCode::OK, "ok"))
+}
+// GET /readyz -> 200 { readiness }, 503 { readiness }
+// The database and its migrations are required, a down cache only degrades
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Ready or degraded", body = ReadinessResponse),
+        (status = 503, description = "Not ready", body = ReadinessResponse),
+    )
+)]
+pub async fn readyz(
+    State(app_state): State<Arc<AppState>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let (status, error) = check(app_state.db.ping()).await;
+    let database = DependencyCheck { status, error };
+
+    let expected = app_state.db.latest_migration();
+    let applied = timeout(CHECK_TIMEOUT, app_state.db.schema_version())
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .flatten();
+    let migrations = MigrationsCheck {
+        status: if applied.is_some_and(|applied| applied >= expected) {
+            CheckStatus::Up
+        } else {
+            CheckStatus::Down
+        },
+        applied,
+        expected,
+    };
+
+    let (status, error) = check(app_state.cache.ping()).await;
+    let cache = CacheCheck {
+        backend: app_state.config.cache.backend,
+        status,
+        error,
+    };
+
+    let readiness = readiness(
+        app_state.shutdown.is_cancelled(),
+        database.status,
+        migrations.status,
+        cache.status,
+    );
+    let status_code = match readiness {
+        Readiness::NotReady => StatusCode::SERVICE_UNAVAILABLE,
+        Readiness::Ready | Readiness::Degraded => StatusCode::OK,
+    };
+    let checks = ReadinessChecks {
+        database,
+        migrations,
+        cache,
+    };
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status: readiness,
+            checks,
+        }),
+    )
+}
+// Readiness of an instance from its checks
+fn readiness(
+    draining: bool,
+    database: CheckStatus,
+    migrations: CheckStatus,
+    cache: CheckStatus,
+) -> Readiness {
+    // draining instances report not ready so load balancers stop sending them traffic
+    if draining || database == CheckStatus::Down || migrations == CheckStatus::Down {
+        Readiness::NotReady
+    } else if cache == CheckStatus::Down {
+        Readiness::Degraded
+    } else {
+        Readiness::Ready
+    }
+}
+// GET /version -> 200 { version, git_sha, schema_version }
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "operations",
+    responses((status = 200, body = VersionResponse))
+)]
+pub async fn version(
+    State(app_state): State<Arc<AppState>>,
+) -> HandlerResult<Json<VersionResponse>> {
+    let schema_version = timeout(CHECK_TIMEOUT, app_state.db.schema_version())
+        .await
+        .ok()
+        .and_then(Result::ok)
+        .flatten();
+    Ok((
+        StatusCode::OK,
+        Json(VersionResponse {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("GIT_SHA"),
+            schema_version,
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use CheckStatus::{Down, Up};
+
+    #[test]
+    fn ready_when_every_check_is_up() {
+        assert_eq!(readiness(false, Up, Up, Up), Readiness::Ready);
+    }
+
+    #[test]
+    fn degraded_when_only_the_cache_is_down() {
+        assert_eq!(readiness(false, Up, Up, Down), Readiness::Degraded);
+    }
+
+    #[test]
+    fn not_ready_without_the_database_or_its_migrations() {
+        for (database, migrations, cache) in [
+            (Down, Up, Up),
+            (Up, Down, Up),
+            (Down, Down, Down),
+            (Up, Down, Down),
+        ] {
+            assert_eq!(
+                readiness(false, database, migrations, cache),
+                Readiness::NotReady,
+                "{database:?} {migrations:?} {cache:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn not_ready_while_draining() {
+        for cache in [Up, Down] {
+            assert_eq!(readiness(true, Up, Up, cache), Readiness::NotReady);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::{
+    expand::{ExpandParams, Expansion},
+    handlers::{common::cached_product_page, HandlerResult},
+    http_cache::LastModified,
+    models::Product,
+    AppState,
+};
+
+// Handlers whose response changed in /api/v2, every other v2 route is served
+// by the v1 handler
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProductPage {
+    // shaped by `include` and `fields`
+    #[schema(value_type = Vec<Product>)]
+    items: Vec<Value>,
+    page: i64,
+    page_size: i64,
+    // null on the last page
+    next_page: Option<i64>,
+}
+// GET /api/v2/products/:page -> 200 { items: product[], page, page_size, next_page }, 400
+#[utoipa::path(
+    get,
+    path = "/products/{page}",
+    tag = "catalogue",
+    params(
+        ("page" = i64, Path, description = "Zero-based page of `catalogue.page_size` products"),
+        ExpandParams,
+    ),
+    responses(
+        (status = 200, body = ProductPage),
+        (status = 304, description = "Unchanged since the `If-None-Match` / `If-Modified-Since` of the request"),
+        (status = 400, description = "Negative `page`, or unknown `include` or `fields`"),
+    )
+)]
+pub async fn product_page(
+    app_state: State<Arc<AppState>>,
+    Path(page): Path<i64>,
+    Query(expand): Query<ExpandParams>,
+) -> HandlerResult<(LastModified, Json<ProductPage>)> {
+    let expansion = Expansion::try_from(expand)?;
+    let products = cached_product_page(&app_state, page).await?;
+    let page_size = app_state.config.catalogue.page_size;
+    // a full page may be followed by an empty one, never by a missing one
+    let next_page = (products.value.len() as i64 == page_size).then(|| page + 1);
+    Ok((
+        StatusCode::OK,
+        (
+            LastModified::from_unix(products.stored_at),
+            Json(ProductPage {
+                items: expansion.products(products.value, &app_state).await?,
+                page,
+                page_size,
+                next_page,
+            }),
+        ),
+    ))
+}
@@ -0,0 +1,249 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, IntoResponseParts, Response, ResponseParts},
+};
+use sha2::{Digest, Sha256};
+
+// When the representation was computed, sent as `Last-Modified`
+#[derive(Debug, Clone, Copy)]
+pub struct LastModified(pub SystemTime);
+impl LastModified {
+    pub fn from_unix(secs: u64) -> Self {
+        Self(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
+impl IntoResponseParts for LastModified {
+    type Error = (StatusCode, String);
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(self.0)) {
+            res.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+        Ok(res)
+    }
+}
+
+// Caching of one route's responses
+#[derive(Debug, Clone)]
+pub struct HttpCaching {
+    pub cache_control: HeaderValue,
+    // larger bodies aren't buffered for an ETag
+    pub etag_max_body: usize,
+}
+
+// Conditional GET for public routes: tags successful responses with an ETag computed
+// from the body and the route's `Cache-Control` policy, and answers `If-None-Match` /
+// `If-Modified-Since` with 304 when the client's copy is current. The ETag is weak:
+// it is computed before compression, so the gzip, brotli and identity encodings of
+// a body share it and aren't byte for byte the same. Bodies above `etag_max_body` or
+// of unknown length are streamed without one and only validated by `Last-Modified`.
+pub async fn conditional_get(
+    State(caching): State<HttpCaching>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let request_headers = request.headers().clone();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    parts
+        .headers
+        .insert(header::CACHE_CONTROL, caching.cache_control);
+    let buffered = body
+        .size_hint()
+        .upper()
+        .is_some_and(|size| size <= caching.etag_max_body as u64);
+    let (body, etag) = if buffered {
+        let body = match to_bytes(body, caching.etag_max_body).await {
+            Ok(body) => body,
+            Err(err) => {
+                return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response()
+            }
+        };
+        let etag = format!("\"{}\"", hex::encode(Sha256::digest(&body)));
+        if let Ok(etag) = HeaderValue::from_str(&format!("W/{etag}")) {
+            parts.headers.insert(header::ETAG, etag);
+        }
+        (Body::from(body), Some(etag))
+    } else {
+        (body, None)
+    };
+
+    if is_not_modified(&request_headers, &parts.headers, etag.as_deref()) {
+        let mut not_modified = Response::new(Body::empty());
+        *not_modified.status_mut() = StatusCode::NOT_MODIFIED;
+        for name in [header::ETAG, header::CACHE_CONTROL, header::LAST_MODIFIED] {
+            if let Some(value) = parts.headers.get(&name) {
+                not_modified.headers_mut().insert(name, value.clone());
+            }
+        }
+        return not_modified;
+    }
+    Response::from_parts(parts, body)
+}
+
+// `etag` is the opaque tag of the response's weak ETag, without `W/`, None when it
+// has none
+fn is_not_modified(request: &HeaderMap, response: &HeaderMap, etag: Option<&str>) -> bool {
+    // If-None-Match takes precedence, If-Modified-Since is only checked without it
+    if let Some(if_none_match) = request.get(header::IF_NONE_MATCH) {
+        let Ok(if_none_match) = if_none_match.to_str() else {
+            return false;
+        };
+        // If-None-Match uses weak comparison, which ignores the W/ prefix of both tags
+        return if_none_match
+            .split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || Some(tag.trim_start_matches("W/")) == etag);
+    }
+    let header_date = |headers: &HeaderMap, name| {
+        headers
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+            .and_then(|value| httpdate::parse_http_date(value).ok())
+    };
+    match (
+        header_date(request, header::IF_MODIFIED_SINCE),
+        header_date(response, header::LAST_MODIFIED),
+    ) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    const BODY: &str = "[1,2,3]";
+    const MODIFIED: &str = "Tue, 20 Oct 2026 10:00:00 GMT";
+
+    fn app(etag_max_body: usize) -> Router {
+        let caching = HttpCaching {
+            cache_control: HeaderValue::from_static("public, max-age=60"),
+            etag_max_body,
+        };
+        Router::new()
+            .route(
+                "/",
+                get(|| async { ([(header::LAST_MODIFIED, MODIFIED)], BODY) })
+                    .post(|| async { BODY }),
+            )
+            .layer(from_fn_with_state(caching, conditional_get))
+    }
+
+    async fn get_with(app: Router, headers: &[(header::HeaderName, &str)]) -> Response {
+        let mut request = Request::get("/");
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn etag() -> String {
+        format!("W/\"{}\"", hex::encode(Sha256::digest(BODY)))
+    }
+
+    #[tokio::test]
+    async fn tags_responses_with_a_weak_etag() {
+        let response = get_with(app(1024), &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::ETAG], etag().as_str());
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=60"
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, BODY);
+    }
+
+    #[tokio::test]
+    async fn if_none_match_answers_304_with_weak_comparison() {
+        let etag = etag();
+        for if_none_match in [
+            etag.clone(),
+            etag.trim_start_matches("W/").to_string(),
+            format!("\"other\", {etag}"),
+            "*".to_string(),
+        ] {
+            let response = get_with(app(1024), &[(header::IF_NONE_MATCH, &if_none_match)]).await;
+            assert_eq!(
+                response.status(),
+                StatusCode::NOT_MODIFIED,
+                "{if_none_match}"
+            );
+            assert_eq!(response.headers()[header::ETAG], etag.as_str());
+            assert_eq!(response.headers()[header::LAST_MODIFIED], MODIFIED);
+        }
+        let response = get_with(app(1024), &[(header::IF_NONE_MATCH, "\"other\"")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn if_modified_since_is_ignored_with_if_none_match() {
+        let response = get_with(
+            app(1024),
+            &[
+                (header::IF_NONE_MATCH, "\"other\""),
+                (header::IF_MODIFIED_SINCE, MODIFIED),
+            ],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn if_modified_since_compares_last_modified() {
+        for (since, status) in [
+            (MODIFIED, StatusCode::NOT_MODIFIED),
+            ("Wed, 21 Oct 2026 10:00:00 GMT", StatusCode::NOT_MODIFIED),
+            ("Mon, 19 Oct 2026 10:00:00 GMT", StatusCode::OK),
+            ("yesterday", StatusCode::OK),
+        ] {
+            let response = get_with(app(1024), &[(header::IF_MODIFIED_SINCE, since)]).await;
+            assert_eq!(response.status(), status, "{since}");
+        }
+    }
+
+    #[tokio::test]
+    async fn large_bodies_get_no_etag() {
+        let response = get_with(app(BODY.len() - 1), &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+        assert!(response.headers().get(header::CACHE_CONTROL).is_some());
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, BODY);
+        // still validated by Last-Modified
+        let response = get_with(
+            app(BODY.len() - 1),
+            &[(header::IF_MODIFIED_SINCE, MODIFIED)],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn other_methods_pass_through() {
+        let response = app(1024)
+            .oneshot(Request::post("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(header::ETAG).is_none());
+        assert!(response.headers().get(header::CACHE_CONTROL).is_none());
+    }
+}
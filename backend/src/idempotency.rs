@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+use utoipa::{
+    openapi::{
+        path::{ParameterBuilder, ParameterIn},
+        OpenApi, Required, ResponseBuilder,
+    },
+    PartialSchema,
+};
+
+use crate::{
+    cache::unix_now,
+    handlers::{admin::AdminSession, internal_error},
+    rate_limit, AppState,
+};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+// Set on responses replayed from the request that first used the key
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+const MAX_KEY_LENGTH: usize = 255;
+
+// Runs a mutating admin request at most once per `Idempotency-Key`, so clients can
+// retry writes that timed out. Keys are scoped by the admin sending them. The
+// first request with a key claims it and its response is kept for
+// `idempotency.window`, retries with the same method, path and body get that
+// response again, and reusing the key for another request or while the first one
+// runs is refused with 409. Requests without the header run as usual.
+pub async fn idempotent(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return next.run(request).await;
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return next.run(request).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                format!("Idempotency-Key must be 1 to {MAX_KEY_LENGTH} visible ASCII characters"),
+            )
+                .into_response()
+        }
+    };
+    // signed in by `admin::admin_auth`, the rate limit client otherwise
+    let client = match request.extensions().get::<AdminSession>() {
+        Some(admin) => format!("admin:{}", admin.admin_id),
+        None => rate_limit::client_key(&request, &app_state.config.rate_limit),
+    };
+    let (parts, body) = request.into_parts();
+    // within `http.body_limit`, enforced by an outer layer
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let fingerprint = fingerprint(&parts.method, &parts.uri, &body);
+
+    let now = unix_now() as i64;
+    let config = &app_state.config;
+    let expired_before = now - config.idempotency.window.as_secs() as i64;
+    // a request still running after the timeout was dropped by it
+    let abandoned_before = now - config.http.request_timeout.as_secs() as i64;
+    let claimed = app_state
+        .db
+        .claim_idempotency_key(
+            &client,
+            &key,
+            &fingerprint,
+            now,
+            expired_before,
+            abandoned_before,
+        )
+        .await;
+    match claimed {
+        Ok(true) => {}
+        Ok(false) => return replay(&app_state, &client, &key, &fingerprint).await,
+        Err(err) => return internal_error(err).into_response(),
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    store(&app_state, &client, &key, response).await
+}
+
+// hex SHA-256 of what makes two requests the same write. The path is the one
+// below `/api/{version}`, so a retry may go to another version or the alias.
+fn fingerprint(method: &Method, uri: &Uri, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_str());
+    hasher.update(b"\n");
+    hasher.update(uri.path_and_query().map_or("", |path| path.as_str()));
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+// Answers a request whose key was already claimed
+async fn replay(app_state: &AppState, client: &str, key: &str, fingerprint: &str) -> Response {
+    let stored = match app_state.db.idempotency_key(client, key).await {
+        Ok(stored) => stored,
+        Err(err) => return internal_error(err).into_response(),
+    };
+    let conflict = |message: &str| (StatusCode::CONFLICT, message.to_string()).into_response();
+    // released by a failed first request since the claim was refused
+    let Some(stored) = stored else {
+        return conflict("A request with this Idempotency-Key just failed, retry it");
+    };
+    if stored.fingerprint != fingerprint {
+        return conflict("Idempotency-Key was already used for a different request");
+    }
+    let (Some(status), Some(body)) = (stored.status, stored.body) else {
+        return conflict("A request with this Idempotency-Key is still in progress");
+    };
+    info!(idempotency_key = %key, "replaying the stored response of a retried request");
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|content_type| HeaderValue::from_str(&content_type).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert(
+        HeaderName::from_static(REPLAYED_HEADER),
+        HeaderValue::from_static("true"),
+    );
+    response
+}
+
+// Keeps the response of the request that claimed `key` for its retries. Client
+// errors are kept like successes, a retry of the same request would fail the same
+// way. Server errors and 409, which reports a state that may pass (e.g. a cache
+// warm already running), aren't: the key is released so a retry runs the request
+// again.
+async fn store(app_state: &AppState, client: &str, key: &str, response: Response) -> Response {
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::CONFLICT {
+        release(app_state, client, key).await;
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => {
+            release(app_state, client, key).await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response();
+        }
+    };
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok());
+    let stored = app_state
+        .db
+        .complete_idempotency_key(
+            client,
+            key,
+            parts.status.as_u16().into(),
+            content_type,
+            &body,
+        )
+        .await;
+    if let Err(err) = stored {
+        // the write is done either way, retries are refused until the claim is abandoned
+        warn!(
+            "storing the response of Idempotency-Key {} failed: {}",
+            key, err
+        );
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn release(app_state: &AppState, client: &str, key: &str) {
+    if let Err(err) = app_state.db.release_idempotency_key(client, key).await {
+        warn!("releasing Idempotency-Key {} failed: {}", key, err);
+    }
+}
+
+// Documents the header and its 409 on the admin writes `idempotent` guards
+pub fn document(api: &mut OpenApi) {
+    for (_, item) in api
+        .paths
+        .paths
+        .iter_mut()
+        .filter(|(path, _)| path.contains("/admin/") && !path.ends_with("/admin/login"))
+    {
+        for operation in [
+            &mut item.post,
+            &mut item.patch,
+            &mut item.put,
+            &mut item.delete,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            let parameter = ParameterBuilder::new()
+                .name("Idempotency-Key")
+                .parameter_in(ParameterIn::Header)
+                .required(Required::False)
+                .description(Some(
+                    "Retries by the same admin with the same key, method, path and body get \
+                     the first response again, for `idempotency.window` seconds. Client errors \
+                     are replayed too, server errors and 409 are not.",
+                ))
+                .schema(Some(String::schema()))
+                .build();
+            operation
+                .parameters
+                .get_or_insert_with(Vec::new)
+                .push(parameter);
+            operation.responses.responses.insert(
+                "409".to_string(),
+                ResponseBuilder::new()
+                    .description(
+                        "The Idempotency-Key was used for another request, or its first \
+                         request is still running",
+                    )
+                    .build()
+                    .into(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{middleware::from_fn_with_state, routing::post, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::config::Config;
+
+    // Routes answering their path's status with the number of times they ran
+    async fn app(runs: &Arc<AtomicUsize>) -> Router {
+        let state = Arc::new(AppState::for_tests(Config::default()).await);
+        let respond = |status: StatusCode| {
+            let runs = runs.clone();
+            move || async move {
+                let run = runs.fetch_add(1, Ordering::SeqCst) + 1;
+                (
+                    status,
+                    [(header::CONTENT_TYPE, "text/plain")],
+                    run.to_string(),
+                )
+            }
+        };
+        Router::new()
+            .route("/created", post(respond(StatusCode::CREATED)))
+            .route("/invalid", post(respond(StatusCode::BAD_REQUEST)))
+            .route("/conflict", post(respond(StatusCode::CONFLICT)))
+            .route("/failed", post(respond(StatusCode::INTERNAL_SERVER_ERROR)))
+            .layer(from_fn_with_state(state, idempotent))
+    }
+
+    async fn send(
+        app: &Router,
+        path: &str,
+        key: Option<&str>,
+        body: &'static str,
+        admin_id: i64,
+    ) -> (StatusCode, bool, String) {
+        let mut request = Request::post(path).extension(AdminSession { admin_id });
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let replayed = response.headers().contains_key(REPLAYED_HEADER);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, replayed, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn retries_get_the_first_response() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        let first = send(&app, "/created", Some("k1"), "{}", 1).await;
+        assert_eq!(first, (StatusCode::CREATED, false, "1".to_string()));
+        let retry = send(&app, "/created", Some("k1"), "{}", 1).await;
+        assert_eq!(retry, (StatusCode::CREATED, true, "1".to_string()));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        // another key runs again
+        let other = send(&app, "/created", Some("k2"), "{}", 1).await;
+        assert_eq!(other, (StatusCode::CREATED, false, "2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn reusing_a_key_for_another_request_is_refused() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        send(&app, "/created", Some("k1"), "{\"a\":1}", 1).await;
+        let (status, replayed, _) = send(&app, "/created", Some("k1"), "{\"a\":2}", 1).await;
+        assert_eq!((status, replayed), (StatusCode::CONFLICT, false));
+        let (status, _, _) = send(&app, "/invalid", Some("k1"), "{\"a\":1}", 1).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn keys_are_scoped_by_admin() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        send(&app, "/created", Some("k1"), "{}", 1).await;
+        let other_admin = send(&app, "/created", Some("k1"), "{}", 2).await;
+        assert_eq!(other_admin, (StatusCode::CREATED, false, "2".to_string()));
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_replayed() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        send(&app, "/invalid", Some("k1"), "{}", 1).await;
+        let retry = send(&app, "/invalid", Some("k1"), "{}", 1).await;
+        assert_eq!(retry, (StatusCode::BAD_REQUEST, true, "1".to_string()));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    // Server errors and 409 release the key, so their retries run again
+    #[tokio::test]
+    async fn server_errors_and_conflicts_are_not_kept() {
+        for (path, status) in [
+            ("/failed", StatusCode::INTERNAL_SERVER_ERROR),
+            ("/conflict", StatusCode::CONFLICT),
+        ] {
+            let runs = Arc::new(AtomicUsize::new(0));
+            let app = app(&runs).await;
+            for run in ["1", "2"] {
+                let response = send(&app, path, Some("k1"), "{}", 1).await;
+                assert_eq!(response, (status, false, run.to_string()), "{path}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_key_always_run() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        for _ in 0..2 {
+            let (_, replayed, _) = send(&app, "/created", None, "{}", 1).await;
+            assert!(!replayed);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn invalid_keys_are_refused() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let app = app(&runs).await;
+        let long = "k".repeat(MAX_KEY_LENGTH + 1);
+        for key in ["", long.as_str()] {
+            let (status, _, _) = send(&app, "/created", Some(key), "{}", 1).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn fingerprints_cover_method_path_and_body() {
+        let uri: Uri = "/admin/product?x=1".parse().unwrap();
+        let original = fingerprint(&Method::POST, &uri, b"{}");
+        assert_eq!(original.len(), 64);
+        assert_ne!(original, fingerprint(&Method::PATCH, &uri, b"{}"));
+        assert_ne!(
+            original,
+            fingerprint(&Method::POST, &"/admin/product".parse().unwrap(), b"{}")
+        );
+        assert_ne!(original, fingerprint(&Method::POST, &uri, b"{ }"));
+    }
+}
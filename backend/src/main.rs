@@ -1,53 +1,97 @@
+mod cache;
+mod config;
+mod db;
+mod deprecation;
+mod expand;
+mod graphql;
 mod handlers;
+mod http_cache;
+mod idempotency;
+mod metrics;
+mod middleware;
 mod models;
-use axum::{
-    extract::{Extension, Json, Path},
-    http::{Response, StatusCode},
-    response::{IntoResponse, Redirect},
-    routing::{self, get, post},
-    Router,
-};
+mod openapi;
+mod rate_limit;
+mod routes;
+mod search;
+mod shutdown;
+mod telemetry;
+mod tls;
+mod typescript;
+mod warm;
 use base64::Engine;
+use clap::Parser;
 use rand::RngCore;
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
-use std::{
-    collections::HashMap,
-    env,
-    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
-    sync::Arc,
-};
+use std::{net::SocketAddr, sync::Arc};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    io::{AsyncBufReadExt, BufReader},
     net::TcpListener,
 };
-use tower_http::trace::TraceLayer;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
 use tracing::info;
 
-use crate::handlers::{
-    admin::{
-        create_admin, create_category, create_product, delete_category, delete_product, login,
-        update_category, update_product,
-    },
-    common::{
-        category_get, parent_categories_get, product_get, product_page, product_search, test,
-    },
+use crate::cache::{
+    memory::MemoryCache, noop::NoopCache, redis::RedisCache, single_flight::SingleFlight, Cache,
 };
+use crate::config::{CacheBackend, Cli, Command, Config, ConfigCommand};
+use crate::db::Database;
+use crate::handlers::admin::create_admin;
+use crate::metrics::Metrics;
+use crate::rate_limit::{memory::MemoryRateLimiter, redis::RedisRateLimiter, RateLimiter};
+use crate::warm::{CacheWarmer, WarmTrigger};
 struct AppState {
-    pg: SqlitePool,
-    redis: deadpool_redis::Pool,
+    db: Box<dyn Database>,
+    cache: Box<dyn Cache>,
+    rate_limiter: Box<dyn RateLimiter>,
+    config: Config,
+    flights: SingleFlight,
+    warmer: CacheWarmer,
+    metrics: Metrics,
+    // cancelled on SIGINT / SIGTERM
+    shutdown: CancellationToken,
+    // background work that shutdown waits for
+    tasks: TaskTracker,
+}
+#[cfg(test)]
+impl AppState {
+    // A migrated in-memory SQLite catalogue with the memory cache and rate limiter
+    async fn for_tests(config: Config) -> Self {
+        let db = db::connect("sqlite::memory:", 1).await.unwrap();
+        db.migrate().await.unwrap();
+        let tasks = TaskTracker::new();
+        Self {
+            db,
+            cache: Box::new(MemoryCache::new(config.cache.capacity)),
+            rate_limiter: Box::new(MemoryRateLimiter::new(config.rate_limit.capacity)),
+            flights: SingleFlight::new(tasks.clone()),
+            warmer: CacheWarmer::new(0),
+            config,
+            metrics: Metrics::new().unwrap(),
+            shutdown: CancellationToken::new(),
+            tasks,
+        }
+    }
 }
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    // Read DB URL and domain from env (provide defaults).
-    let database_url =
-        env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:catalogue.db".to_string());
-    // Create SQLite pool
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url)
-        .await?;
-    let args = std::env::args().collect::<Vec<String>>();
-    if args.get(1).is_some() {
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
+    if let Some(Command::Config {
+        command: ConfigCommand::Show,
+    }) = cli.command
+    {
+        print!("{}", config.redacted_toml()?);
+        return Ok(());
+    }
+    if let Some(Command::Types { check, out }) = &cli.command {
+        let out = out
+            .clone()
+            .unwrap_or_else(|| typescript::DEFAULT_OUTPUT.into());
+        return typescript::generate(&out, *check);
+    }
+    // SQLite or Postgres, by the scheme of the URL
+    let db = db::connect(&config.database.url, config.database.max_connections).await?;
+    if let Some(Command::CreateAdmin) = cli.command {
         let stdin = tokio::io::stdin();
         let mut reader = BufReader::new(stdin);
 
@@ -63,67 +107,105 @@ async fn main() -> anyhow::Result<()> {
         let password = password.trim().to_string();
 
         println!("creating admin: {}, {}", &username, &password);
-        create_admin(pool, username, password).await?;
+        create_admin(db.as_ref(), username, password, config.auth.bcrypt_cost).await?;
 
         println!("admin created!");
         return Ok(());
     }
 
-    tracing_subscriber::fmt::init();
-    sqlx::migrate!("./migrations").run(&pool).await?;
-    // Create a Redis pool
-    let cfg = deadpool_redis::Config::from_url("redis://127.0.0.1");
-    let redis = cfg
-        .create_pool(Some(deadpool_redis::Runtime::Tokio1))
-        .expect("failed to create redis pool");
-    // Ensure table exists
-    let state = Arc::new(AppState { pg: pool, redis });
-    // let protected = Router::new().route("/category/:category_id", routing::delete(delete_category));
-    // let admin_routes = Router::new();
-    // let category_routes = Router::new();
-    // let product_routes = Router::new();
-    let port: u16 = env::var("AXUM_PORT")
-        .unwrap_or("3000".to_string())
-        .parse()?;
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    let admin_router = Router::new()
-        .route(
-            "/category",
-            routing::delete(delete_category)
-                .patch(update_category)
-                .post(create_category),
-        )
-        .route(
-            "/product",
-            routing::delete(delete_product)
-                .patch(update_product)
-                .post(create_product),
-        )
-        .route("/login", post(login));
-    // merge routers
-    let app = Router::new()
-        // .route("/", post(test))
-        .route("/category/{id}", get(category_get))
-        .route("/categories", get(parent_categories_get))
-        .route("/product/{id}", get(product_get))
-        .route("/product/search/{query}/{page}", get(product_search))
-        .route("/products/{page}", get(product_page))
-        .route("/test/{test}", get(test))
-        .nest("/admin", admin_router)
-        // .nest("/categories", category_routes)
-        // .nest("/product", product_routes)
-        //
-        .with_state(state);
+    let _telemetry = telemetry::init(&config.logging)?;
+    db.migrate().await?;
+    let cache_config = &config.cache;
+    let redis = match cache_config.backend {
+        CacheBackend::Redis => {
+            let cfg = deadpool_redis::Config {
+                url: Some(cache_config.redis_url.clone()),
+                connection: None,
+                pool: Some(deadpool_redis::PoolConfig::new(
+                    cache_config.redis_pool_size,
+                )),
+            };
+            Some(
+                cfg.create_pool(Some(deadpool_redis::Runtime::Tokio1))
+                    .expect("failed to create redis pool"),
+            )
+        }
+        CacheBackend::Memory | CacheBackend::None => None,
+    };
+    let cache: Box<dyn Cache> = match (&redis, cache_config.backend) {
+        (Some(redis), _) => Box::new(RedisCache::new(redis.clone(), &cache_config.key_prefix)),
+        (None, CacheBackend::Memory) => Box::new(MemoryCache::new(cache_config.capacity)),
+        (None, _) => Box::new(NoopCache),
+    };
+    // shared by every instance through Redis when the cache uses it
+    let rate_limiter: Box<dyn RateLimiter> = match redis {
+        Some(redis) => Box::new(RedisRateLimiter::new(redis, &cache_config.key_prefix)),
+        None => Box::new(MemoryRateLimiter::new(config.rate_limit.capacity)),
+    };
+    let warm_config = config.cache.warm.clone();
+    let addr = SocketAddr::from((config.server.bind, config.server.port));
+    let shutdown_timeout = config.server.shutdown_timeout;
+    let tls_config = config.server.tls.clone();
+    let rustls = if tls_config.enabled {
+        Some(tls::load(&tls_config).await?)
+    } else {
+        None
+    };
+    let tasks = TaskTracker::new();
+    let state = Arc::new(AppState {
+        db,
+        cache,
+        rate_limiter,
+        flights: SingleFlight::new(tasks.clone()),
+        warmer: CacheWarmer::new(warm_config.product_pages),
+        config,
+        metrics: Metrics::new()?,
+        shutdown: CancellationToken::new(),
+        tasks,
+    });
+    tokio::spawn(shutdown::on_signal(state.shutdown.clone()));
+    if warm_config.on_startup {
+        warm::start(&state, WarmTrigger::Startup);
+    }
+    if let Some(interval) = warm_config.interval() {
+        warm::schedule(state.clone(), interval);
+    }
+    let app = routes::router(&state);
     // let app = Router::new()
     //     .route("/test/{test}", get(test))
     //     .with_state(state.clone());
-    info!("listening on http://{}", addr);
-    // axum::Server::bind(&addr)
-    //     .serve(app.into_make_service())
-    //     .await?;
-    let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-    Ok(())
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    let Some(rustls) = rustls else {
+        info!("listening on http://{}", addr);
+        // axum::Server::bind(&addr)
+        //     .serve(app.into_make_service())
+        //     .await?;
+        let listener = TcpListener::bind(addr).await?;
+        // stops accepting connections on shutdown, then waits for open ones to finish
+        let server = axum::serve(listener, app)
+            .with_graceful_shutdown(state.shutdown.clone().cancelled_owned());
+        return shutdown::serve(server, &state, shutdown_timeout).await;
+    };
+    tls::watch(rustls.clone(), &tls_config, &state);
+    if tls_config.redirect_port != 0 {
+        let redirect_addr = SocketAddr::from((addr.ip(), tls_config.redirect_port));
+        tls::redirect(redirect_addr, addr.port(), &state);
+    }
+    info!("listening on https://{}", addr);
+    // same as above, the handle stops accepting and lets open connections finish
+    let handle = axum_server::Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        let shutdown = state.shutdown.clone();
+        async move {
+            shutdown.cancelled().await;
+            handle.graceful_shutdown(None);
+        }
+    });
+    let server = axum_server::bind_rustls(addr, rustls)
+        .handle(handle)
+        .serve(app);
+    shutdown::serve(server, &state, shutdown_timeout).await
 }
 pub fn generate_token() -> String {
     let mut bytes = [0u8; 32]; // 256-bit token
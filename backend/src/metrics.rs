@@ -0,0 +1,184 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tracing::warn;
+
+use crate::{handlers::internal_error, AppState};
+
+// Prometheus collectors of the process, rendered by `GET /metrics`
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    cache_lookups: IntCounterVec,
+    logins: IntCounterVec,
+    rate_limited: IntCounterVec,
+    deprecated_requests: IntCounterVec,
+    db_connections: IntGaugeVec,
+    products: IntGauge,
+    categories: IntGauge,
+}
+impl Metrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests by route and status"),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency by route",
+            ),
+            &["method", "route"],
+        )?;
+        let cache_lookups = IntCounterVec::new(
+            Opts::new(
+                "cache_lookups_total",
+                "Response cache lookups by route and result (hit, miss, error)",
+            ),
+            &["route", "result"],
+        )?;
+        let logins = IntCounterVec::new(
+            Opts::new("admin_logins_total", "Admin logins by result"),
+            &["result"],
+        )?;
+        let rate_limited = IntCounterVec::new(
+            Opts::new(
+                "rate_limited_requests_total",
+                "Requests rejected with 429 by rate limit scope",
+            ),
+            &["scope"],
+        )?;
+        let deprecated_requests = IntCounterVec::new(
+            Opts::new(
+                "deprecated_requests_total",
+                "Requests to the unversioned aliases of /api/v1 by route",
+            ),
+            &["method", "route"],
+        )?;
+        let db_connections = IntGaugeVec::new(
+            Opts::new(
+                "db_pool_connections",
+                "Database pool connections by state (active, idle, max)",
+            ),
+            &["state"],
+        )?;
+        let products = IntGauge::new("catalogue_products", "Products in the catalogue")?;
+        let categories = IntGauge::new("catalogue_categories", "Categories in the catalogue")?;
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+        registry.register(Box::new(logins.clone()))?;
+        registry.register(Box::new(rate_limited.clone()))?;
+        registry.register(Box::new(deprecated_requests.clone()))?;
+        registry.register(Box::new(db_connections.clone()))?;
+        registry.register(Box::new(products.clone()))?;
+        registry.register(Box::new(categories.clone()))?;
+        Ok(Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            cache_lookups,
+            logins,
+            rate_limited,
+            deprecated_requests,
+            db_connections,
+            products,
+            categories,
+        })
+    }
+    // `key` is a `CacheKey`, its route keeps the label set small
+    pub fn cache_lookup(&self, key: &str, result: &str) {
+        let route = key.split('?').next().unwrap_or(key);
+        self.cache_lookups.with_label_values(&[route, result]).inc();
+    }
+    pub fn login(&self, success: bool) {
+        let result = if success { "success" } else { "failure" };
+        self.logins.with_label_values(&[result]).inc();
+    }
+    pub fn rate_limited(&self, scope: &str) {
+        self.rate_limited.with_label_values(&[scope]).inc();
+    }
+    pub fn deprecated_request(&self, method: &str, route: &str) {
+        self.deprecated_requests
+            .with_label_values(&[method, route])
+            .inc();
+    }
+}
+
+// Counts and times every matched route, labelled with the route template
+// rather than the path so ids and queries don't create new series
+pub async fn track_requests(
+    State(app_state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let method = request.method().to_string();
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let metrics = &app_state.metrics;
+    metrics
+        .http_request_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
+
+// GET /metrics -> 200 text/plain (Prometheus exposition format)
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses((status = 200, description = "Prometheus exposition format", body = String, content_type = "text/plain"))
+)]
+pub async fn metrics(State(app_state): State<Arc<AppState>>) -> Response {
+    let metrics = &app_state.metrics;
+    // gauges are sampled on scrape
+    let pool = app_state.db.pool_status();
+    for (state, value) in [
+        ("active", pool.size.saturating_sub(pool.idle)),
+        ("idle", pool.idle),
+        ("max", pool.max),
+    ] {
+        metrics
+            .db_connections
+            .with_label_values(&[state])
+            .set(value.into());
+    }
+    match app_state.db.catalogue_counts().await {
+        Ok((products, categories)) => {
+            metrics.products.set(products);
+            metrics.categories.set(categories);
+        }
+        Err(err) => warn!("failed to count the catalogue for metrics: {}", err),
+    }
+    let mut body = Vec::new();
+    if let Err(err) = TextEncoder::new().encode(&metrics.registry.gather(), &mut body) {
+        return internal_error(err).into_response();
+    }
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        body,
+    )
+        .into_response()
+}
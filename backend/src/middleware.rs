@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use axum::http::{header, HeaderName, HeaderValue, Method, StatusCode};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    set_header::SetResponseHeaderLayer,
+    timeout::TimeoutLayer,
+};
+
+use crate::{
+    config::HttpConfig, deprecation, idempotency, rate_limit, telemetry::REQUEST_ID_HEADER,
+};
+
+const HSTS: &str = "max-age=31536000; includeSubDomains";
+
+// Lets the configured origins call the API, with the admin session cookie when
+// credentials are allowed
+pub fn cors(config: &HttpConfig) -> CorsLayer {
+    let request_id = HeaderName::from_static(REQUEST_ID_HEADER);
+    let origins = &config.cors_allowed_origins;
+    // origins are validated when the configuration is loaded
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::IF_NONE_MATCH,
+            header::IF_MODIFIED_SINCE,
+            request_id.clone(),
+            HeaderName::from_static(rate_limit::API_KEY_HEADER),
+            HeaderName::from_static(idempotency::IDEMPOTENCY_KEY_HEADER),
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::LAST_MODIFIED,
+            header::RETRY_AFTER,
+            request_id,
+            HeaderName::from_static(rate_limit::LIMIT_HEADER),
+            HeaderName::from_static(rate_limit::REMAINING_HEADER),
+            HeaderName::from_static(rate_limit::RESET_HEADER),
+            HeaderName::from_static(deprecation::DEPRECATION_HEADER),
+            HeaderName::from_static(idempotency::REPLAYED_HEADER),
+            HeaderName::from_static(deprecation::SUNSET_HEADER),
+            header::LINK,
+        ])
+        .allow_credentials(config.cors_allow_credentials)
+        .max_age(Duration::from_secs(600))
+}
+
+// Standard security headers, HSTS with TLS or `http.hsts`. Handlers can still set
+// their own.
+pub fn security_headers(
+    config: &HttpConfig,
+    tls: bool,
+) -> Vec<SetResponseHeaderLayer<HeaderValue>> {
+    let mut headers = vec![
+        (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+        (header::REFERRER_POLICY, "strict-origin-when-cross-origin"),
+        (header::X_FRAME_OPTIONS, "DENY"),
+    ];
+    if config.hsts || tls {
+        headers.push((header::STRICT_TRANSPORT_SECURITY, HSTS));
+    }
+    headers
+        .into_iter()
+        .map(|(name, value)| {
+            SetResponseHeaderLayer::if_not_present(name, HeaderValue::from_static(value))
+        })
+        .collect()
+}
+
+// Answers 408 when a request takes longer than `request_timeout`
+pub fn timeout(config: &HttpConfig) -> TimeoutLayer {
+    TimeoutLayer::with_status_code(StatusCode::REQUEST_TIMEOUT, config.request_timeout)
+}
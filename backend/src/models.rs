@@ -1,7 +1,10 @@
+use async_graphql::SimpleObject;
 use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct Category {
     pub id: i64,
     pub name: String,
@@ -9,7 +12,8 @@ pub struct Category {
     pub parent_id: Option<i64>,
 }
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema, SimpleObject)]
+#[graphql(complex)]
 pub struct Product {
     pub id: i64,
     pub name: String,
@@ -20,6 +24,7 @@ pub struct Product {
     // pub updated_at: NaiveDateTime,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ProductCategory {
     pub product_id: i64,
@@ -71,6 +76,73 @@ impl TryFrom<&SubCategory> for Category {
         }
     }
 }
+// Rows of the queries run for several categories at once, tagged with the
+// category they were asked for
+#[derive(Debug, FromRow)]
+pub struct CategoryAncestor {
+    pub of_category: i64,
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub parent_id: Option<i64>,
+}
+impl From<CategoryAncestor> for (i64, Category) {
+    fn from(row: CategoryAncestor) -> Self {
+        (
+            row.of_category,
+            Category {
+                id: row.id,
+                name: row.name,
+                description: row.description,
+                parent_id: row.parent_id,
+            },
+        )
+    }
+}
+#[derive(Debug, FromRow)]
+pub struct CategoryProduct {
+    pub of_category: i64,
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: i64,
+    pub category_id: Option<i64>,
+}
+impl From<CategoryProduct> for (i64, Product) {
+    fn from(row: CategoryProduct) -> Self {
+        (
+            row.of_category,
+            Product {
+                id: row.id,
+                name: row.name,
+                description: row.description,
+                price: row.price,
+                category_id: row.category_id,
+            },
+        )
+    }
+}
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SynonymGroup {
+    pub id: i64,
+    pub terms: Vec<String>,
+}
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Synonym {
+    pub term: String,
+    pub group_id: i64,
+}
+// An `Idempotency-Key` of the admin routes, the response is None while the
+// request that claimed it runs
+#[derive(Debug, FromRow)]
+pub struct IdempotencyKey {
+    // hex SHA-256 of the method, path and body of the request
+    pub fingerprint: String,
+    pub status: Option<i64>,
+    pub content_type: Option<String>,
+    pub body: Option<Vec<u8>>,
+}
+#[allow(dead_code)]
 pub struct Token {
     pub token: String,
     pub admin_id: String,
@@ -79,30 +151,77 @@ pub struct Token {
 pub mod request {
     pub mod delete {
         use serde::{Deserialize, Serialize};
-        #[derive(Debug, Serialize, Deserialize)]
+        use utoipa::ToSchema;
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = DeleteProduct)]
         pub struct Product {
             pub product_id: i64,
         }
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = DeleteCategory)]
         pub struct Category {
             pub category_id: i64,
         }
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = DeleteSynonymGroup)]
+        pub struct SynonymGroup {
+            pub group_id: i64,
+        }
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = DeleteStopWord)]
+        pub struct StopWord {
+            pub word: String,
+        }
     }
     pub mod create {
+        use async_graphql::InputObject;
         use serde::{Deserialize, Serialize};
+        use utoipa::ToSchema;
 
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Serialize, Deserialize, ToSchema, InputObject)]
+        #[schema(as = NewProduct)]
+        #[graphql(name = "NewProduct")]
         pub struct Product {
             pub name: String,
             pub description: Option<String>,
             pub price: i64,
             pub category_id: Option<i64>,
         }
-        #[derive(Debug, Serialize, Deserialize)]
+        #[derive(Debug, Serialize, Deserialize, ToSchema, InputObject)]
+        #[schema(as = NewCategory)]
+        #[graphql(name = "NewCategory")]
         pub struct Category {
             pub name: String,
             pub description: Option<String>,
             pub parent_id: Option<i64>,
         }
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = NewSynonymGroup)]
+        pub struct SynonymGroup {
+            pub terms: Vec<String>,
+        }
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[schema(as = NewStopWord)]
+        pub struct StopWord {
+            pub word: String,
+        }
+    }
+    pub mod bulk {
+        use serde::{Deserialize, Serialize};
+        use utoipa::ToSchema;
+
+        // One write of `POST /admin/bulk`, the body of the single-item route with
+        // an `op` naming it: `{ "op": "update_product", "id": 1, "name": .. }`
+        #[derive(Debug, Serialize, Deserialize, ToSchema)]
+        #[serde(tag = "op", rename_all = "snake_case")]
+        #[schema(as = BulkOperation)]
+        pub enum Operation {
+            CreateProduct(super::create::Product),
+            UpdateProduct(crate::models::Product),
+            DeleteProduct(super::delete::Product),
+            CreateCategory(super::create::Category),
+            UpdateCategory(crate::models::Category),
+            DeleteCategory(super::delete::Category),
+        }
     }
 }
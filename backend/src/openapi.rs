@@ -0,0 +1,204 @@
+use utoipa::OpenApi;
+
+// Document served at `/openapi.json` and browsable at `/docs`. Operations are
+// added by the router from each handler's `#[utoipa::path]`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Product catalogue",
+        description = "Public catalogue reads are cacheable: they carry `ETag`, `Last-Modified` and \
+            `Cache-Control`, and answer conditional requests with 304. Every route but the \
+            operations ones is rate limited per client and reports its budget in `RateLimit-*` \
+            headers, exhausted budgets get 429 with `Retry-After`."
+    ),
+    tags(
+        (name = "catalogue", description = "Categories, products and search"),
+        (name = "admin", description = "Catalogue, search and cache administration"),
+        (name = "operations", description = "Health checks, version and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+        Router,
+    };
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::{config::Config, routes, AppState};
+
+    // Session token of the admin `app` creates
+    const TOKEN: &str = "session";
+
+    async fn app() -> Router {
+        let mut config = Config::default();
+        config.rate_limit.enabled = false;
+        let state = Arc::new(AppState::for_tests(config).await);
+        let db = &state.db;
+        db.create_admin("admin", "").await.unwrap();
+        let admin = db.admin_by_username("admin").await.unwrap().unwrap();
+        db.create_token(TOKEN, admin.id).await.unwrap();
+        // tells unmatched paths apart from handlers answering 404
+        routes::router(&state).fallback(|| async { StatusCode::IM_A_TEAPOT })
+    }
+
+    async fn spec(app: &Router) -> Value {
+        let response = app
+            .clone()
+            .oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    // A value of the type the spec gives a path parameter
+    fn sample(parameters: &[Value], name: &str) -> &'static str {
+        let schema_type = parameters
+            .iter()
+            .find(|parameter| parameter["name"] == name && parameter["in"] == "path")
+            .unwrap_or_else(|| panic!("path parameter {name} is not documented"))["schema"]["type"]
+            .as_str();
+        match schema_type {
+            Some("integer") => "1",
+            _ => "tee",
+        }
+    }
+
+    // Every documented operation is routed to a handler that accepts the documented
+    // path and required query parameters: a path or method the router doesn't serve, or a parameter
+    // documented with another type than its handler extracts, fails the test.
+    #[tokio::test]
+    async fn spec_matches_routes() {
+        let app = app().await;
+        let spec = spec(&app).await;
+        let paths = spec["paths"].as_object().unwrap();
+        assert!(!paths.is_empty());
+        for (path, item) in paths {
+            for (method, operation) in item.as_object().unwrap() {
+                let parameters = operation["parameters"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+                let uri = path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix('{') {
+                        Some(name) => sample(&parameters, name.trim_end_matches('}')),
+                        None => segment,
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                // required query parameters get their documented example
+                let query: Vec<String> = parameters
+                    .iter()
+                    .filter(|parameter| parameter["in"] == "query" && parameter["required"] == true)
+                    .map(|parameter| {
+                        let example = parameter["example"].as_str().unwrap_or_else(|| {
+                            panic!("query parameter {} has no example", parameter["name"])
+                        });
+                        format!("{}={}", parameter["name"].as_str().unwrap(), example)
+                    })
+                    .collect();
+                let uri = if query.is_empty() {
+                    uri
+                } else {
+                    format!("{uri}?{}", query.join("&"))
+                };
+                let method: Method = method.to_uppercase().parse().unwrap();
+                let mut request = Request::builder()
+                    .method(&method)
+                    .uri(&uri)
+                    .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"));
+                // an empty object reaches the handler's extractor, invalid or not
+                let body = if operation.get("requestBody").is_some() {
+                    request = request.header(header::CONTENT_TYPE, "application/json");
+                    Body::from("{}")
+                } else {
+                    Body::empty()
+                };
+                let response = app
+                    .clone()
+                    .oneshot(request.body(body).unwrap())
+                    .await
+                    .unwrap();
+                let status = response.status();
+                assert!(
+                    ![
+                        StatusCode::IM_A_TEAPOT,
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        StatusCode::BAD_REQUEST,
+                    ]
+                    .contains(&status),
+                    "{method} {path} is documented but {method} {uri} answered {status}"
+                );
+            }
+        }
+    }
+
+    // Every admin operation but the login answers 401 without a known session token
+    #[tokio::test]
+    async fn admin_routes_require_a_session() {
+        let app = app().await;
+        let spec = spec(&app).await;
+        let admin_paths = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .filter(|(path, _)| path.contains("/admin/") && !path.ends_with("/admin/login"));
+        for (path, item) in admin_paths {
+            for (method, operation) in item.as_object().unwrap() {
+                assert!(
+                    operation["responses"].get("401").is_some(),
+                    "{method} {path} doesn't document its 401"
+                );
+                let method: Method = method.to_uppercase().parse().unwrap();
+                for authorization in [None, Some("Bearer unknown")] {
+                    let mut request = Request::builder().method(&method).uri(path);
+                    if let Some(authorization) = authorization {
+                        request = request.header(header::AUTHORIZATION, authorization);
+                    }
+                    let response = app
+                        .clone()
+                        .oneshot(request.body(Body::empty()).unwrap())
+                        .await
+                        .unwrap();
+                    assert_eq!(
+                        response.status(),
+                        StatusCode::UNAUTHORIZED,
+                        "{method} {path} with {authorization:?}"
+                    );
+                }
+            }
+        }
+        // the login's cookie is accepted as well
+        let response = app
+            .oneshot(
+                Request::get("/api/v1/admin/search/stop-words")
+                    .header(header::COOKIE, format!("theme=dark; auth_token={TOKEN}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn docs_are_served() {
+        let response = app()
+            .await
+            .oneshot(Request::get("/docs/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
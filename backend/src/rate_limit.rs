@@ -0,0 +1,390 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    config::{RateBudget, RateLimitConfig},
+    AppState,
+};
+
+pub mod memory;
+pub mod redis;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const LIMIT_HEADER: &str = "ratelimit-limit";
+pub const REMAINING_HEADER: &str = "ratelimit-remaining";
+pub const RESET_HEADER: &str = "ratelimit-reset";
+
+// Token buckets keyed by client and scope. Every request takes a token, buckets
+// hold up to `burst` tokens and refill at `per_minute`.
+#[async_trait]
+pub trait RateLimiter: Send + Sync {
+    // Takes a token from the bucket of `key` when one is left
+    async fn acquire(&self, key: &str, budget: RateBudget) -> anyhow::Result<Decision>;
+}
+
+// Budget a route draws from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateScope {
+    Search,
+    Read,
+    Admin,
+}
+impl RateScope {
+    fn name(self) -> &'static str {
+        match self {
+            RateScope::Search => "search",
+            RateScope::Read => "read",
+            RateScope::Admin => "admin",
+        }
+    }
+    // Whether requests are refused rather than let through unlimited when the
+    // limiter fails: admin writes, logins included, are never left unlimited
+    fn fails_closed(self) -> bool {
+        self == RateScope::Admin
+    }
+    fn budget(self, config: &RateLimitConfig) -> RateBudget {
+        match self {
+            RateScope::Search => config.search,
+            RateScope::Read => config.read,
+            RateScope::Admin => config.admin,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    // whole tokens left
+    pub remaining: u32,
+    // seconds until the bucket is full again
+    pub reset: u64,
+    // seconds until the next token, 0 when allowed
+    pub retry_after: u64,
+}
+impl Decision {
+    // `tokens` is what the bucket holds after the request
+    fn new(budget: RateBudget, allowed: bool, tokens: f64) -> Self {
+        let rate = refill_rate(budget);
+        let retry_after = if allowed {
+            0
+        } else {
+            ((1.0 - tokens) / rate).ceil().max(1.0) as u64
+        };
+        Self {
+            allowed,
+            limit: budget.burst,
+            remaining: tokens.floor() as u32,
+            reset: ((budget.burst as f64 - tokens) / rate).ceil().max(0.0) as u64,
+            retry_after,
+        }
+    }
+}
+
+// Tokens per second
+fn refill_rate(budget: RateBudget) -> f64 {
+    budget.per_minute as f64 / 60.0
+}
+
+// Refills a bucket that held `tokens` `elapsed` seconds ago and takes a token from
+// it, returns whether one was taken and the tokens left
+fn take(budget: RateBudget, tokens: f64, elapsed: f64) -> (bool, f64) {
+    let tokens = (tokens + elapsed.max(0.0) * refill_rate(budget)).min(budget.burst as f64);
+    if tokens >= 1.0 {
+        (true, tokens - 1.0)
+    } else {
+        (false, tokens)
+    }
+}
+
+// Limits the routes it wraps to the budget of `scope`, per client. Admin routes
+// draw reads from the read budget. When the limiter fails admin writes get 503,
+// other requests go through.
+pub async fn limit(
+    State((app_state, scope)): State<(Arc<AppState>, RateScope)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = &app_state.config.rate_limit;
+    if !config.enabled {
+        return next.run(request).await;
+    }
+    let scope = match scope {
+        RateScope::Admin if request.method() == Method::GET || request.method() == Method::HEAD => {
+            RateScope::Read
+        }
+        scope => scope,
+    };
+    let client = client_key(&request, config);
+    let decision = match acquire(&app_state, scope, &client).await {
+        Ok(Some(decision)) => decision,
+        Ok(None) => return next.run(request).await,
+        Err(err) => return err.into_response(),
+    };
+    if !decision.allowed {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "rate limit exceeded, retry in {} seconds",
+                decision.retry_after
+            ),
+        )
+            .into_response();
+        set_headers(response.headers_mut(), &decision);
+        return response;
+    }
+    let mut response = next.run(request).await;
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+// Takes a token of `scope` from the bucket of `client`. None when rate limiting is
+// off or the limiter failed on a scope that lets requests through, 503 when it
+// failed on one that doesn't.
+pub async fn acquire(
+    app_state: &AppState,
+    scope: RateScope,
+    client: &str,
+) -> Result<Option<Decision>, (StatusCode, String)> {
+    let config = &app_state.config.rate_limit;
+    if !config.enabled {
+        return Ok(None);
+    }
+    let key = format!("{}:{}", scope.name(), client);
+    match app_state
+        .rate_limiter
+        .acquire(&key, scope.budget(config))
+        .await
+    {
+        Ok(decision) => {
+            if !decision.allowed {
+                app_state.metrics.rate_limited(scope.name());
+            }
+            Ok(Some(decision))
+        }
+        Err(err) if scope.fails_closed() => {
+            warn!(
+                "rate limiter failed, refusing the {} request: {:#}",
+                scope.name(),
+                err
+            );
+            Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "rate limiter unavailable, retry later".to_string(),
+            ))
+        }
+        Err(err) => {
+            warn!(
+                "rate limiter failed, letting the request through: {:#}",
+                err
+            );
+            Ok(None)
+        }
+    }
+}
+
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    for (name, value) in [
+        (LIMIT_HEADER, decision.limit as u64),
+        (REMAINING_HEADER, decision.remaining as u64),
+        (RESET_HEADER, decision.reset),
+    ] {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+    if !decision.allowed {
+        headers.insert(
+            axum::http::header::RETRY_AFTER,
+            HeaderValue::from(decision.retry_after),
+        );
+    }
+}
+
+// A configured API key, hashed so keys never reach Redis, or else the client address
+pub fn client_key(request: &Request, config: &RateLimitConfig) -> String {
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|key| key.to_str().ok())
+        .filter(|key| config.api_keys.iter().any(|known| known == key));
+    if let Some(api_key) = api_key {
+        let hash = Sha256::digest(api_key.as_bytes());
+        return format!("key:{}", hex::encode(&hash[..8]));
+    }
+    match client_ip(request, config) {
+        Some(ip) => format!("ip:{ip}"),
+        None => "ip:unknown".to_string(),
+    }
+}
+
+fn client_ip(request: &Request, config: &RateLimitConfig) -> Option<IpAddr> {
+    if config.trust_forwarded_for {
+        // the last address is the one the proxy saw, earlier ones are client supplied
+        let forwarded = request
+            .headers()
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .next_back()
+            .and_then(|ip| ip.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroUsize, sync::Arc};
+
+    use axum::{body::Body, middleware::from_fn_with_state, routing::get, Router};
+    use tower::ServiceExt;
+
+    use super::{memory::MemoryRateLimiter, *};
+    use crate::config::Config;
+
+    // 2 tokens, one more every 2 seconds
+    const BUDGET: RateBudget = RateBudget {
+        burst: 2,
+        per_minute: 30,
+    };
+
+    #[test]
+    fn take_refills_up_to_the_burst() {
+        assert_eq!(take(BUDGET, 2.0, 0.0), (true, 1.0));
+        assert_eq!(take(BUDGET, 0.5, 0.0), (false, 0.5));
+        // half a token after a second
+        assert_eq!(take(BUDGET, 0.5, 1.0), (true, 0.0));
+        assert_eq!(take(BUDGET, 0.0, 3600.0), (true, 1.0));
+        // clocks going backwards don't drain the bucket
+        assert_eq!(take(BUDGET, 1.0, -5.0), (true, 0.0));
+    }
+
+    #[test]
+    fn decisions_report_the_bucket() {
+        let allowed = Decision::new(BUDGET, true, 1.0);
+        assert!(allowed.allowed);
+        assert_eq!(
+            (
+                allowed.limit,
+                allowed.remaining,
+                allowed.reset,
+                allowed.retry_after
+            ),
+            (2, 1, 2, 0)
+        );
+        let refused = Decision::new(BUDGET, false, 0.5);
+        assert!(!refused.allowed);
+        assert_eq!(
+            (refused.remaining, refused.reset, refused.retry_after),
+            (0, 3, 1)
+        );
+        let empty = Decision::new(BUDGET, false, 0.0);
+        assert_eq!(empty.retry_after, 2);
+    }
+
+    #[test]
+    fn headers_carry_the_decision() {
+        let mut headers = HeaderMap::new();
+        set_headers(&mut headers, &Decision::new(BUDGET, true, 1.0));
+        assert_eq!(headers[LIMIT_HEADER], "2");
+        assert_eq!(headers[REMAINING_HEADER], "1");
+        assert_eq!(headers[RESET_HEADER], "2");
+        assert!(headers.get(axum::http::header::RETRY_AFTER).is_none());
+        set_headers(&mut headers, &Decision::new(BUDGET, false, 0.0));
+        assert_eq!(headers[REMAINING_HEADER], "0");
+        assert_eq!(headers[axum::http::header::RETRY_AFTER], "2");
+    }
+
+    struct FailingLimiter;
+    #[async_trait]
+    impl RateLimiter for FailingLimiter {
+        async fn acquire(&self, _key: &str, _budget: RateBudget) -> anyhow::Result<Decision> {
+            anyhow::bail!("redis is down")
+        }
+    }
+
+    async fn app(rate_limiter: Box<dyn RateLimiter>, scope: RateScope) -> Router {
+        let mut config = Config::default();
+        config.rate_limit.read = BUDGET;
+        config.rate_limit.admin = RateBudget {
+            burst: 1,
+            per_minute: 1,
+        };
+        let mut state = AppState::for_tests(config).await;
+        state.rate_limiter = rate_limiter;
+        Router::new()
+            .route("/", get(|| async { "ok" }).post(|| async { "ok" }))
+            .layer(from_fn_with_state((Arc::new(state), scope), limit))
+    }
+
+    fn memory_limiter() -> Box<dyn RateLimiter> {
+        Box::new(MemoryRateLimiter::new(NonZeroUsize::new(10).unwrap()))
+    }
+
+    async fn send(app: &Router, method: Method) -> Response {
+        let request = axum::http::Request::builder()
+            .method(method)
+            .uri("/")
+            .body(Body::empty())
+            .unwrap();
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn exhausted_budgets_get_429() {
+        let app = app(memory_limiter(), RateScope::Read).await;
+        for remaining in ["1", "0"] {
+            let response = send(&app, Method::GET).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[REMAINING_HEADER], remaining);
+        }
+        let response = send(&app, Method::GET).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[axum::http::header::RETRY_AFTER], "2");
+    }
+
+    // Admin reads draw from the read budget, writes from the admin one
+    #[tokio::test]
+    async fn admin_reads_use_the_read_budget() {
+        let app = app(memory_limiter(), RateScope::Admin).await;
+        assert_eq!(send(&app, Method::GET).await.headers()[LIMIT_HEADER], "2");
+        let write = send(&app, Method::POST).await;
+        assert_eq!(write.headers()[LIMIT_HEADER], "1");
+        assert_eq!(
+            send(&app, Method::POST).await.status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failing_limiter_refuses_only_admin_writes() {
+        let reads = app(Box::new(FailingLimiter), RateScope::Read).await;
+        let response = send(&reads, Method::GET).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(LIMIT_HEADER).is_none());
+
+        let admin = app(Box::new(FailingLimiter), RateScope::Admin).await;
+        assert_eq!(send(&admin, Method::GET).await.status(), StatusCode::OK);
+        assert_eq!(
+            send(&admin, Method::POST).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::{num::NonZeroUsize, sync::Mutex, time::Instant};
+
+use async_trait::async_trait;
+use lru::LruCache;
+
+use crate::{
+    config::RateBudget,
+    rate_limit::{take, Decision, RateLimiter},
+};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Buckets of the least recently seen clients are dropped past `capacity`, which
+// hands them a full bucket. Buckets are local to this instance, so every instance
+// grants the full budget.
+pub struct MemoryRateLimiter {
+    buckets: Mutex<LruCache<String, Bucket>>,
+}
+impl MemoryRateLimiter {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            buckets: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+#[async_trait]
+impl RateLimiter for MemoryRateLimiter {
+    async fn acquire(&self, key: &str, budget: RateBudget) -> anyhow::Result<Decision> {
+        let now = Instant::now();
+        let mut buckets = self
+            .buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let bucket = buckets.get_or_insert_mut(key.to_string(), || Bucket {
+            tokens: budget.burst as f64,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        let (allowed, tokens) = take(budget, bucket.tokens, elapsed);
+        bucket.tokens = tokens;
+        bucket.updated = now;
+        Ok(Decision::new(budget, allowed, tokens))
+    }
+}
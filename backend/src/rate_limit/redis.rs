@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use redis::Script;
+use tracing::instrument;
+
+use crate::{
+    cache::redis::key_prefix,
+    config::RateBudget,
+    rate_limit::{refill_rate, Decision, RateLimiter},
+};
+
+// Refills and takes from the bucket atomically, with the clock of Redis so every
+// instance agrees on it. Mirrors `rate_limit::take`, the bucket expires once full.
+const ACQUIRE: &str = r"
+local burst = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) + tonumber(time[2]) / 1000000
+local bucket = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+local tokens = tonumber(bucket[1]) or burst
+local updated = tonumber(bucket[2]) or now
+tokens = math.min(burst, tokens + math.max(0, now - updated) * rate)
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', tostring(now))
+redis.call('PEXPIRE', KEYS[1], math.ceil((burst - tokens) / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+";
+
+// Buckets shared by every instance using the same Redis and key prefix
+pub struct RedisRateLimiter {
+    pool: deadpool_redis::Pool,
+    prefix: String,
+    script: Script,
+}
+impl RedisRateLimiter {
+    pub fn new(pool: deadpool_redis::Pool, prefix: &str) -> Self {
+        Self {
+            pool,
+            prefix: format!("{}ratelimit:", key_prefix(prefix)),
+            script: Script::new(ACQUIRE),
+        }
+    }
+}
+#[async_trait]
+impl RateLimiter for RedisRateLimiter {
+    #[instrument(skip(self), fields(cache.backend = "redis"), err)]
+    async fn acquire(&self, key: &str, budget: RateBudget) -> anyhow::Result<Decision> {
+        let mut conn = self.pool.get().await?;
+        let (allowed, tokens): (i64, String) = self
+            .script
+            .key(format!("{}{}", self.prefix, key))
+            .arg(budget.burst)
+            .arg(refill_rate(budget))
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(Decision::new(budget, allowed == 1, tokens.parse()?))
+    }
+}
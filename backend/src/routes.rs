@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::DefaultBodyLimit, http::HeaderValue, middleware::from_fn_with_state, routing::get,
+    Extension, Router,
+};
+use tower_http::{
+    compression::CompressionLayer,
+    limit::RequestBodyLimitLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use utoipa::OpenApi;
+use utoipa_axum::{
+    router::{OpenApiRouter, UtoipaMethodRouterExt},
+    routes,
+};
+use utoipa_swagger_ui::SwaggerUi;
+
+use crate::{
+    deprecation, graphql,
+    handlers::{admin, common, common::test, health, v2},
+    http_cache::{conditional_get, HttpCaching},
+    idempotency,
+    metrics::{self, track_requests},
+    middleware,
+    openapi::ApiDoc,
+    rate_limit::{self, RateScope},
+    telemetry, AppState,
+};
+
+// Versions of the API, served under `/api/{name}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ApiVersion {
+    V1,
+    // breaking changes to v1 responses, e.g. the pagination envelope of `/products/{page}`
+    V2,
+}
+impl ApiVersion {
+    fn name(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "v1",
+            ApiVersion::V2 => "v2",
+        }
+    }
+}
+
+// The whole API with its middleware. Routes are registered from their
+// `#[utoipa::path]`, so `/openapi.json` documents exactly what is served.
+pub fn router(state: &Arc<AppState>) -> Router {
+    let config = &state.config;
+    let track_requests = || from_fn_with_state(state.clone(), track_requests);
+    // merge routers
+    let (app, mut api) = OpenApiRouter::with_openapi(ApiDoc::openapi())
+        // .route("/", post(test))
+        .nest("/api/v1", versioned(state, ApiVersion::V1))
+        .nest("/api/v2", versioned(state, ApiVersion::V2))
+        .route("/test/{test}", get(test))
+        .route(
+            "/graphql",
+            get(graphql::graphiql)
+                .post(graphql::execute)
+                .layer(Extension(graphql::schema())),
+        )
+        .routes(routes!(health::healthz))
+        .routes(routes!(health::readyz))
+        .routes(routes!(health::version))
+        .routes(routes!(metrics::metrics))
+        // after every route so the matched route template is known
+        .route_layer(track_requests())
+        .split_for_parts();
+    // v2 documents the v1 handlers it still uses again, their operation ids must
+    // stay unique
+    let v2 = format!("/api/{}/", ApiVersion::V2.name());
+    for (_, item) in api
+        .paths
+        .paths
+        .iter_mut()
+        .filter(|(path, _)| path.starts_with(&v2))
+    {
+        for operation in [
+            &mut item.get,
+            &mut item.post,
+            &mut item.patch,
+            &mut item.put,
+            &mut item.delete,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(id) = &mut operation.operation_id {
+                *id = format!("v2_{id}");
+            }
+        }
+    }
+    admin::document(&mut api);
+    idempotency::document(&mut api);
+    let mut app = app.merge(SwaggerUi::new("/docs").url("/openapi.json", api));
+    if config.api.legacy_routes {
+        // the routes as they were before versioning, undocumented and deprecated
+        let (legacy, _) = versioned(state, ApiVersion::V1)
+            .route_layer(from_fn_with_state(state.clone(), deprecation::legacy_alias))
+            .route_layer(track_requests())
+            .split_for_parts();
+        app = app.merge(legacy);
+    }
+    let http_config = &config.http;
+    let mut app = app
+        .layer(RequestBodyLimitLayer::new(http_config.body_limit))
+        // the layer above enforces the configured limit instead
+        .layer(DefaultBodyLimit::disable())
+        .layer(middleware::timeout(http_config));
+    if http_config.compression {
+        app = app.layer(CompressionLayer::new());
+    }
+    for layer in middleware::security_headers(http_config, config.server.tls.enabled) {
+        app = app.layer(layer);
+    }
+    app.layer(middleware::cors(http_config))
+        // outermost last: take or generate the request id, open the request span
+        // with it, and echo it back on the response
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::request_span))
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state.clone())
+}
+
+// The catalogue and admin routes of one version of the API
+fn versioned(state: &Arc<AppState>, version: ApiVersion) -> OpenApiRouter<Arc<AppState>> {
+    let http_cache = &state.config.http_cache;
+    // conditional GET with the route's Cache-Control policy, validated when the config was loaded
+    let http_caching = |policy: &str| {
+        let caching = HttpCaching {
+            cache_control: HeaderValue::from_str(policy).expect("invalid Cache-Control policy"),
+            etag_max_body: http_cache.etag_max_body,
+        };
+        from_fn_with_state(caching, conditional_get)
+    };
+    // per client budgets, the probes and /metrics are never limited
+    let rate_limited =
+        |scope: RateScope| from_fn_with_state((state.clone(), scope), rate_limit::limit);
+    // let protected = Router::new().route("/category/:category_id", routing::delete(delete_category));
+    // let admin_routes = Router::new();
+    // let category_routes = Router::new();
+    // let product_routes = Router::new();
+    let admin_router = OpenApiRouter::new()
+        .routes(routes!(
+            admin::delete_category,
+            admin::update_category,
+            admin::create_category
+        ))
+        .routes(routes!(
+            admin::delete_product,
+            admin::update_product,
+            admin::create_product
+        ))
+        .routes(routes!(
+            admin::synonyms_get,
+            admin::delete_synonym_group,
+            admin::update_synonym_group,
+            admin::create_synonym_group
+        ))
+        .routes(routes!(
+            admin::stop_words_get,
+            admin::delete_stop_word,
+            admin::create_stop_word
+        ))
+        .routes(routes!(admin::bulk))
+        .routes(routes!(admin::purge_cache))
+        .routes(routes!(admin::warm_cache_progress, admin::warm_cache))
+        // every admin write but the login, whose session token must not be replayed
+        .route_layer(from_fn_with_state(state.clone(), idempotency::idempotent))
+        // signed in before a write claims its idempotency key
+        .route_layer(from_fn_with_state(state.clone(), admin::admin_auth))
+        .routes(routes!(admin::login))
+        .route_layer(rate_limited(RateScope::Admin));
+    let product_page = match version {
+        ApiVersion::V1 => routes!(common::product_page),
+        ApiVersion::V2 => routes!(v2::product_page),
+    };
+    OpenApiRouter::new()
+        .routes(
+            routes!(common::category_get)
+                .layer(http_caching(&http_cache.category))
+                .layer(rate_limited(RateScope::Read)),
+        )
+        .routes(
+            routes!(common::parent_categories_get)
+                .layer(http_caching(&http_cache.categories))
+                .layer(rate_limited(RateScope::Read)),
+        )
+        .routes(
+            routes!(common::products_get, common::products_post)
+                .layer(http_caching(&http_cache.products))
+                .layer(rate_limited(RateScope::Read)),
+        )
+        .routes(
+            routes!(common::product_get)
+                .layer(http_caching(&http_cache.product))
+                .layer(rate_limited(RateScope::Read)),
+        )
+        .routes(
+            routes!(common::product_search)
+                .layer(http_caching(&http_cache.search))
+                .layer(rate_limited(RateScope::Search)),
+        )
+        .routes(
+            product_page
+                .layer(http_caching(&http_cache.products))
+                .layer(rate_limited(RateScope::Read)),
+        )
+        .nest("/admin", admin_router)
+    // .nest("/categories", category_routes)
+    // .nest("/product", product_routes)
+}
@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{db::Database, models::Synonym};
+
+// Lowercase + trim so stored synonyms, stop words and queries compare equal
+pub fn normalise_term(term: &str) -> String {
+    term.trim().to_lowercase()
+}
+
+pub fn tokenize(query: &str) -> Vec<String> {
+    query.split_whitespace().map(normalise_term).collect()
+}
+
+// true when any token of `query` is one of `terms`
+pub fn query_mentions(query: &str, terms: &HashSet<String>) -> bool {
+    tokenize(query).iter().any(|token| terms.contains(token))
+}
+
+// Expands a search query into groups of alternative terms.
+// Stop words are dropped and every remaining token is replaced by its synonym group,
+// a product matches when its name contains at least one term of every group.
+pub async fn expand_query(db: &dyn Database, query: &str) -> Result<Vec<Vec<String>>, sqlx::Error> {
+    let stop_words: HashSet<String> = db.stop_words().await?.into_iter().collect();
+    let synonyms = db.synonyms().await?;
+    Ok(expand(query, &stop_words, &synonyms))
+}
+
+fn expand(query: &str, stop_words: &HashSet<String>, synonyms: &[Synonym]) -> Vec<Vec<String>> {
+    let mut groups: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut term_groups: HashMap<&str, i64> = HashMap::new();
+    for synonym in synonyms {
+        groups
+            .entry(synonym.group_id)
+            .or_default()
+            .push(synonym.term.clone());
+        term_groups.insert(&synonym.term, synonym.group_id);
+    }
+    let tokens = tokenize(query);
+    let expanded: Vec<Vec<String>> = tokens
+        .iter()
+        .filter(|token| !stop_words.contains(*token))
+        .map(|token| match term_groups.get(token.as_str()) {
+            Some(group_id) => groups[group_id].clone(),
+            None => vec![token.clone()],
+        })
+        .collect();
+    if expanded.is_empty() && !tokens.is_empty() {
+        // a query made only of stop words still searches for itself
+        return vec![vec![tokens.join(" ")]];
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, models::request};
+
+    fn synonyms(groups: &[&[&str]]) -> Vec<Synonym> {
+        groups
+            .iter()
+            .zip(1..)
+            .flat_map(|(terms, group_id)| {
+                terms.iter().map(move |term| Synonym {
+                    term: term.to_string(),
+                    group_id,
+                })
+            })
+            .collect()
+    }
+
+    fn stop_words(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn tokens_are_normalised() {
+        assert_eq!(tokenize("  Red\tT-Shirt  "), ["red", "t-shirt"]);
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn tokens_expand_to_their_synonym_group() {
+        let synonyms = synonyms(&[&["tee", "t-shirt"], &["red", "crimson"]]);
+        assert_eq!(
+            expand("Red TEE cotton", &HashSet::new(), &synonyms),
+            [
+                vec!["red", "crimson"],
+                vec!["tee", "t-shirt"],
+                vec!["cotton"]
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_words_are_dropped() {
+        let synonyms = synonyms(&[&["tee", "t-shirt"]]);
+        assert_eq!(
+            expand(
+                "a tee for the beach",
+                &stop_words(&["a", "for", "the"]),
+                &synonyms
+            ),
+            [vec!["tee", "t-shirt"], vec!["beach"]]
+        );
+    }
+
+    #[test]
+    fn a_query_of_stop_words_searches_for_itself() {
+        assert_eq!(
+            expand("The Who", &stop_words(&["the", "who"]), &[]),
+            [vec!["the who"]]
+        );
+        assert!(expand("", &stop_words(&["the"]), &[]).is_empty());
+    }
+
+    #[test]
+    fn query_mentions_whole_tokens() {
+        let terms = stop_words(&["tee"]);
+        assert!(query_mentions("Red TEE", &terms));
+        assert!(!query_mentions("teepee", &terms));
+    }
+
+    // The stored synonyms and stop words expand a query, and every group must match
+    #[tokio::test]
+    async fn search_matches_synonyms() {
+        let db = db::connect("sqlite::memory:", 1).await.unwrap();
+        db.migrate().await.unwrap();
+        db.create_synonym_group(&["tee".to_string(), "t-shirt".to_string()])
+            .await
+            .unwrap();
+        db.create_stop_word("the").await.unwrap();
+        for name in ["Red t-shirt", "Blue tee", "Red hoodie"] {
+            let product = request::create::Product {
+                name: name.to_string(),
+                description: None,
+                price: 10,
+                category_id: None,
+            };
+            db.create_product(&product).await.unwrap();
+        }
+
+        let groups = expand_query(db.as_ref(), "the red tee").await.unwrap();
+        assert_eq!(groups, [vec!["red"], vec!["t-shirt", "tee"]]);
+        let products = db.search_products(&groups, 10, 0).await.unwrap();
+        let names: Vec<&str> = products
+            .iter()
+            .map(|product| product.name.as_str())
+            .collect();
+        assert_eq!(names, ["Red t-shirt"]);
+
+        let groups = expand_query(db.as_ref(), "TEE").await.unwrap();
+        let products = db.search_products(&groups, 10, 0).await.unwrap();
+        assert_eq!(products.len(), 2);
+    }
+}
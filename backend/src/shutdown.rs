@@ -0,0 +1,84 @@
+use std::{future::IntoFuture, io, sync::Arc, time::Duration};
+
+use tokio::{
+    signal,
+    time::{timeout_at, Instant},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::AppState;
+
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Cancels `shutdown` on SIGINT (ctrl-c) or SIGTERM
+pub async fn on_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(err) = signal::ctrl_c().await {
+            warn!("failed to listen for ctrl-c: {}", err);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(err) => {
+                warn!("failed to listen for SIGTERM: {}", err);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => info!("received ctrl-c"),
+        _ = terminate => info!("received SIGTERM"),
+    }
+    shutdown.cancel();
+}
+
+// Runs `server` until shutdown, then drains it: in-flight requests and background
+// tasks get until `timeout` after the signal to finish, then the pools are closed.
+// `server` must stop accepting connections once `app_state.shutdown` is cancelled.
+pub async fn serve<S>(server: S, app_state: &Arc<AppState>, timeout: Duration) -> anyhow::Result<()>
+where
+    S: IntoFuture<Output = io::Result<()>>,
+{
+    let server = server.into_future();
+    tokio::pin!(server);
+    tokio::select! {
+        // the server returns as soon as it is told to stop, draining must still run
+        biased;
+        _ = app_state.shutdown.cancelled() => {}
+        result = &mut server => return Ok(result?),
+    }
+    info!("shutting down, draining requests for up to {:?}", timeout);
+    let deadline = Instant::now() + timeout;
+    match timeout_at(deadline, &mut server).await {
+        Ok(result) => result?,
+        Err(_) => {
+            warn!("requests still running at the shutdown deadline, closing their connections")
+        }
+    }
+    app_state.tasks.close();
+    if timeout_at(deadline, app_state.tasks.wait()).await.is_err() {
+        warn!(
+            "{} background tasks still running at the shutdown deadline",
+            app_state.tasks.len()
+        );
+    }
+    app_state.cache.close().await;
+    // connections return quickly once requests and tasks are done, this only bounds
+    // waiting on the ones abandoned at the deadline
+    if tokio::time::timeout(POOL_CLOSE_TIMEOUT, app_state.db.close())
+        .await
+        .is_err()
+    {
+        warn!("database connections still in use, closing without them");
+    }
+    info!("shutdown complete");
+    Ok(())
+}
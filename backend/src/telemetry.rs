@@ -0,0 +1,80 @@
+use axum::{body::Body, http::Request};
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use tracing::{info_span, Span};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+use crate::config::{LogFormat, LoggingConfig};
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Keeps the span exporter alive, buffered spans are flushed when it is dropped
+pub struct Telemetry {
+    provider: Option<SdkTracerProvider>,
+}
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(err) = provider.shutdown() {
+                eprintln!("failed to flush traces: {err}");
+            }
+        }
+    }
+}
+
+// Installs the global subscriber: text or JSON logs filtered by `logging.filter`,
+// plus OTLP span export when an endpoint is configured
+pub fn init(config: &LoggingConfig) -> anyhow::Result<Telemetry> {
+    let filter = EnvFilter::try_new(&config.filter)?;
+    let logs = match config.format {
+        LogFormat::Text => fmt::layer().boxed(),
+        // the span list carries the request id into every line of a request
+        LogFormat::Json => fmt::layer().json().with_span_list(true).boxed(),
+    };
+    let provider = if config.otlp_endpoint.is_empty() {
+        None
+    } else {
+        let endpoint = format!("{}/v1/traces", config.otlp_endpoint.trim_end_matches('/'));
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(endpoint)
+            .build()?;
+        let resource = Resource::builder()
+            .with_service_name(config.service_name.clone())
+            .build();
+        Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(resource)
+                .build(),
+        )
+    };
+    let traces = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+    });
+    tracing_subscriber::registry()
+        .with(logs)
+        .with(traces)
+        .with(filter)
+        .try_init()?;
+    if provider.is_some() {
+        tracing::info!("exporting traces to {}", config.otlp_endpoint);
+    }
+    Ok(Telemetry { provider })
+}
+
+// Root span of a request, tagged with the id set by the request id layers
+pub fn request_span(request: &Request<Body>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        request_id = %request_id,
+    )
+}
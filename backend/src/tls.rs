@@ -0,0 +1,130 @@
+use std::{net::SocketAddr, path::Path, sync::Arc, time::SystemTime};
+
+use axum::{
+    extract::Request,
+    http::{header, uri::Authority, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use tokio::{net::TcpListener, time::MissedTickBehavior};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::{config::TlsConfig, AppState};
+
+// Loads the certificate chain and key of `config`
+pub async fn load(config: &TlsConfig) -> anyhow::Result<RustlsConfig> {
+    // one process wide provider, installing it twice is harmless
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(&config.cert_path, &config.key_path)
+        .await
+        .map_err(|err| anyhow::anyhow!("failed to load the TLS certificate and key: {err}"))
+}
+
+// Reloads the certificate and key whenever either file changes, so renewed
+// certificates are served without a restart. New connections use the new
+// certificate, a pair that fails to load keeps the previous one in use.
+pub fn watch(rustls: RustlsConfig, config: &TlsConfig, app_state: &Arc<AppState>) {
+    if config.reload_interval.is_zero() {
+        return;
+    }
+    let config = config.clone();
+    let shutdown = app_state.shutdown.clone();
+    let watch = async move {
+        let mut last_modified = modified(&config).await;
+        let mut interval = tokio::time::interval(config.reload_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        interval.tick().await;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => return,
+            }
+            let current = modified(&config).await;
+            if current == last_modified {
+                continue;
+            }
+            match rustls
+                .reload_from_pem_file(&config.cert_path, &config.key_path)
+                .await
+            {
+                Ok(()) => {
+                    info!("reloaded the TLS certificate");
+                    last_modified = current;
+                }
+                // retried on the next tick, the key may not be written yet
+                Err(err) => warn!("failed to reload the TLS certificate: {}", err),
+            }
+        }
+    };
+    app_state
+        .tasks
+        .spawn(watch.instrument(info_span!("tls_reload")));
+}
+
+// Modification times of the certificate and key, None when unreadable
+async fn modified(config: &TlsConfig) -> [Option<SystemTime>; 2] {
+    async fn mtime(path: &Path) -> Option<SystemTime> {
+        tokio::fs::metadata(path).await.ok()?.modified().ok()
+    }
+    [
+        mtime(&config.cert_path).await,
+        mtime(&config.key_path).await,
+    ]
+}
+
+// Serves `redirect_port` in plain HTTP, answering every request with a permanent
+// redirect to the same URL over HTTPS on `https_port`, until shutdown
+pub fn redirect(addr: SocketAddr, https_port: u16, app_state: &Arc<AppState>) {
+    let shutdown = app_state.shutdown.clone();
+    let redirect = async move {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("failed to bind the HTTPS redirect on {}: {}", addr, err);
+                return;
+            }
+        };
+        info!("redirecting http://{} to HTTPS", addr);
+        let app = Router::new()
+            .fallback(move |request: Request| async move { to_https(request, https_port) });
+        if let Err(err) = axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown.cancelled_owned())
+            .await
+        {
+            warn!("HTTPS redirect failed: {}", err);
+        }
+    };
+    app_state
+        .tasks
+        .spawn(redirect.instrument(info_span!("https_redirect")));
+}
+
+fn to_https(request: Request, https_port: u16) -> Response {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok());
+    let Some(host) = host else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    let authority = match https_port {
+        443 => host.host().to_string(),
+        port => format!("{}:{}", host.host(), port),
+    };
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    match Uri::builder()
+        .scheme("https")
+        .authority(authority)
+        .path_and_query(path)
+        .build()
+    {
+        Ok(uri) => Redirect::permanent(&uri.to_string()).into_response(),
+        Err(_) => (StatusCode::BAD_REQUEST, "invalid Host header").into_response(),
+    }
+}
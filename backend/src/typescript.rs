@@ -0,0 +1,177 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use serde_json::Value;
+use utoipa::OpenApi;
+
+use crate::{
+    handlers::{
+        admin::{LoginPayload, PurgeResponse},
+        common::{GetCategoryResponse, ProductBatch, ProductIds},
+        v2::ProductPage,
+    },
+    models::{request, Category, Product, SynonymGroup},
+    warm::{WarmProgress, WarmTrigger},
+};
+
+// Where the frontend imports the generated types from
+pub const DEFAULT_OUTPUT: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../frontend/src/types/backend.ts"
+);
+
+const HEADER: &str = "\
+// Generated from the backend models by `cargo run -- types`, do not edit.
+// `cargo run -- types --check` and `cargo test` fail when this file is out of date.
+";
+
+// Types the frontend sends or receives, with the schemas the OpenAPI document uses
+#[derive(OpenApi)]
+#[openapi(components(schemas(
+    Product,
+    Category,
+    SynonymGroup,
+    GetCategoryResponse,
+    ProductPage,
+    ProductBatch,
+    ProductIds,
+    LoginPayload,
+    PurgeResponse,
+    WarmProgress,
+    WarmTrigger,
+    request::create::Product,
+    request::create::Category,
+    request::create::SynonymGroup,
+    request::create::StopWord,
+    request::delete::Product,
+    request::delete::Category,
+    request::delete::SynonymGroup,
+    request::delete::StopWord,
+)))]
+struct FrontendTypes;
+
+// Writes the TypeScript definitions to `output`, or with `check` fails when the
+// file there differs from them
+pub fn generate(output: &Path, check: bool) -> anyhow::Result<()> {
+    let rendered = render();
+    if check {
+        let current = fs::read_to_string(output)
+            .with_context(|| format!("failed to read {}", output.display()))?;
+        if current != rendered {
+            anyhow::bail!(
+                "{} is out of date, regenerate it with `cargo run -- types`",
+                output.display()
+            );
+        }
+        return Ok(());
+    }
+    fs::write(output, rendered).with_context(|| format!("failed to write {}", output.display()))
+}
+
+fn render() -> String {
+    let schemas = serde_json::to_value(FrontendTypes::openapi().components)
+        .expect("schemas serialize to JSON")["schemas"]
+        .take();
+    let mut out = HEADER.to_string();
+    // sorted by name, so the output only changes with the types
+    for (name, schema) in schemas.as_object().into_iter().flatten() {
+        out.push('\n');
+        out.push_str(&declaration(name, schema));
+    }
+    out
+}
+
+fn declaration(name: &str, schema: &Value) -> String {
+    match schema.get("properties") {
+        Some(_) => format!("export interface {name} {}\n", object(schema, 0)),
+        None => format!("export type {name} = {};\n", ts_type(schema, 0)),
+    }
+}
+
+fn object(schema: &Value, depth: usize) -> String {
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    let indent = "  ".repeat(depth + 1);
+    let mut out = "{\n".to_string();
+    for (field, field_schema) in schema["properties"].as_object().into_iter().flatten() {
+        let ts = ts_type(field_schema, depth + 1);
+        // Option fields are serialized as null rather than left out, so they
+        // are typed `T | null` instead of optional
+        let optional = !required.contains(&field.as_str()) && !ts.ends_with("| null");
+        let marker = if optional { "?" } else { "" };
+        out.push_str(&format!("{indent}{field}{marker}: {ts};\n"));
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push('}');
+    out
+}
+
+fn ts_type(schema: &Value, depth: usize) -> String {
+    if let Some(reference) = schema["$ref"].as_str() {
+        return reference
+            .rsplit('/')
+            .next()
+            .unwrap_or(reference)
+            .to_string();
+    }
+    if let Some(variants) = schema["oneOf"].as_array() {
+        return union(variants.iter().map(|variant| ts_type(variant, depth)));
+    }
+    if let Some(values) = schema["enum"].as_array() {
+        return union(values.iter().map(Value::to_string));
+    }
+    match &schema["type"] {
+        Value::String(primitive) => ts_primitive(primitive, schema, depth),
+        Value::Array(types) => union(
+            types
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|primitive| ts_primitive(primitive, schema, depth)),
+        ),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn ts_primitive(primitive: &str, schema: &Value, depth: usize) -> String {
+    match primitive {
+        "string" => "string".to_string(),
+        "integer" | "number" => "number".to_string(),
+        "boolean" => "boolean".to_string(),
+        "null" => "null".to_string(),
+        "array" => {
+            let item = ts_type(&schema["items"], depth);
+            if item.contains(' ') {
+                format!("({item})[]")
+            } else {
+                format!("{item}[]")
+            }
+        }
+        "object" if schema.get("properties").is_some() => object(schema, depth),
+        _ => "unknown".to_string(),
+    }
+}
+
+// `null` goes last whatever the order of the schema
+fn union(types: impl Iterator<Item = String>) -> String {
+    let (nulls, mut types): (Vec<String>, Vec<String>) = types.partition(|ts| ts == "null");
+    types.extend(nulls.into_iter().take(1));
+    types.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    // The check mode of `cargo run -- types`, so a model change without the
+    // regenerated frontend types fails the build
+    #[test]
+    fn frontend_types_are_current() {
+        if let Err(err) = super::generate(Path::new(super::DEFAULT_OUTPUT), true) {
+            panic!("{err:#}");
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use tracing::{info, info_span, warn, Instrument};
+use utoipa::ToSchema;
+
+use crate::{
+    cache::unix_now,
+    handlers::common::{
+        cached_parent_categories, category_key, product_page_key, warm_category,
+        warm_parent_categories, warm_product_page,
+    },
+    AppState,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmTrigger {
+    Startup,
+    Schedule,
+    Admin,
+    Purge,
+    Bulk,
+}
+impl WarmTrigger {
+    // Purges and bulk writes only refill the responses they dropped, the other
+    // triggers recompute every one
+    fn refills(self) -> bool {
+        matches!(self, WarmTrigger::Purge | WarmTrigger::Bulk)
+    }
+}
+
+// State of the current or last warm-up, reported by `GET /admin/cache/warm`
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct WarmProgress {
+    pub running: bool,
+    pub trigger: Option<WarmTrigger>,
+    // responses to compute, known once the category tree is loaded
+    pub total: usize,
+    pub done: usize,
+    pub failed: usize,
+    // unix seconds
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+}
+
+// Precomputes the responses first visitors would otherwise pay for: the category
+// tree, the page of every top-level category and the first product pages
+pub struct CacheWarmer {
+    product_pages: i64,
+    progress: Mutex<WarmProgress>,
+}
+impl CacheWarmer {
+    pub fn new(product_pages: i64) -> Self {
+        Self {
+            product_pages,
+            progress: Mutex::new(WarmProgress::default()),
+        }
+    }
+    pub fn progress(&self) -> WarmProgress {
+        self.lock().clone()
+    }
+    fn lock(&self) -> std::sync::MutexGuard<'_, WarmProgress> {
+        self.progress
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    // Marks a warm-up as started, false when one is already running
+    fn begin(&self, trigger: WarmTrigger) -> bool {
+        let mut progress = self.lock();
+        if progress.running {
+            return false;
+        }
+        *progress = WarmProgress {
+            running: true,
+            trigger: Some(trigger),
+            started_at: Some(unix_now()),
+            ..WarmProgress::default()
+        };
+        true
+    }
+    fn record<T>(&self, what: &str, result: Result<T, (StatusCode, String)>) {
+        let mut progress = self.lock();
+        match result {
+            Ok(_) => progress.done += 1,
+            Err((_, err)) => {
+                warn!("warming {} failed: {}", what, err);
+                progress.failed += 1;
+            }
+        }
+    }
+    fn finish(&self) {
+        let mut progress = self.lock();
+        progress.running = false;
+        progress.finished_at = Some(unix_now());
+        info!(
+            "cache warming finished: {}/{} responses, {} failed",
+            progress.done, progress.total, progress.failed
+        );
+    }
+}
+
+// Starts warming the cache in the background, false when a warm-up is already running
+pub fn start(app_state: &Arc<AppState>, trigger: WarmTrigger) -> bool {
+    if !app_state.warmer.begin(trigger) {
+        return false;
+    }
+    info!("warming cache ({:?})", trigger);
+    let span = info_span!("cache_warm", ?trigger);
+    let tasks = &app_state.tasks;
+    let app_state = app_state.clone();
+    tasks.spawn(
+        async move {
+            warm(&app_state, trigger).await;
+            app_state.warmer.finish();
+        }
+        .instrument(span),
+    );
+    true
+}
+
+// Warms the cache every `interval`, skipping runs that would overlap
+pub fn schedule(app_state: Arc<AppState>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // the first tick completes immediately, startup warming covers it
+        ticker.tick().await;
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = app_state.shutdown.cancelled() => return,
+            }
+            start(&app_state, WarmTrigger::Schedule);
+        }
+    });
+}
+
+// A response warmed after the category tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Category(i64),
+    ProductPage(i64),
+}
+impl Target {
+    fn key(self, app_state: &AppState) -> String {
+        match self {
+            Target::Category(id) => category_key(id),
+            Target::ProductPage(page) => {
+                product_page_key(page, app_state.config.catalogue.page_size)
+            }
+        }
+    }
+    async fn warm(self, app_state: &Arc<AppState>) {
+        let warmer = &app_state.warmer;
+        match self {
+            Target::Category(id) => {
+                let result = warm_category(app_state, id).await;
+                warmer.record(&format!("/category/{id}"), result);
+            }
+            Target::ProductPage(page) => {
+                let result = warm_product_page(app_state, page).await;
+                warmer.record(&format!("/products/{page}"), result);
+            }
+        }
+    }
+}
+
+async fn warm(app_state: &Arc<AppState>, trigger: WarmTrigger) {
+    let warmer = &app_state.warmer;
+    // the tree comes first, it lists the top-level categories to warm. A refill
+    // keeps it when it is still cached.
+    let top_level = if trigger.refills() {
+        cached_parent_categories(app_state).await.map(|tree| {
+            tree.value
+                .iter()
+                .map(|category| category.id)
+                .collect::<Vec<_>>()
+        })
+    } else {
+        warm_parent_categories(app_state).await
+    };
+    let top_level = match top_level {
+        Ok(top_level) => top_level,
+        Err(err) => {
+            warmer.lock().total = 1;
+            warmer.record::<()>("/categories", Err(err));
+            return;
+        }
+    };
+    let targets = targets(app_state, top_level, trigger.refills()).await;
+    {
+        let mut progress = warmer.lock();
+        progress.total = 1 + targets.len();
+        progress.done = 1;
+    }
+    for target in targets {
+        target.warm(app_state).await;
+    }
+}
+
+// The top-level categories and the first product pages, on a refill only those
+// missing from the cache
+async fn targets(app_state: &AppState, top_level: Vec<i64>, refill: bool) -> Vec<Target> {
+    let targets: Vec<Target> = top_level
+        .into_iter()
+        .map(Target::Category)
+        .chain((0..app_state.warmer.product_pages).map(Target::ProductPage))
+        .collect();
+    if !refill {
+        return targets;
+    }
+    let keys: Vec<String> = targets.iter().map(|target| target.key(app_state)).collect();
+    match app_state.cache.get_many(&keys).await {
+        Ok(cached) => targets
+            .into_iter()
+            .zip(cached)
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(target, _)| target)
+            .collect(),
+        Err(err) => {
+            warn!("listing the cached responses to refill failed: {:#}", err);
+            targets
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cache::CacheTag, config::Config, models::request};
+
+    // Top-level categories 1 and 2, and one product page to warm
+    async fn state() -> Arc<AppState> {
+        let mut state = AppState::for_tests(Config::default()).await;
+        state.warmer = CacheWarmer::new(1);
+        for name in ["Shirts", "Shoes"] {
+            let category = request::create::Category {
+                name: name.to_string(),
+                description: None,
+                parent_id: None,
+            };
+            state.db.create_category(&category).await.unwrap();
+        }
+        Arc::new(state)
+    }
+
+    async fn run(state: &Arc<AppState>, trigger: WarmTrigger) -> WarmProgress {
+        assert!(state.warmer.begin(trigger));
+        warm(state, trigger).await;
+        state.warmer.finish();
+        state.warmer.progress()
+    }
+
+    const ALL: [Target; 3] = [
+        Target::Category(1),
+        Target::Category(2),
+        Target::ProductPage(0),
+    ];
+
+    #[tokio::test]
+    async fn warm_ups_compute_the_tree_categories_and_pages() {
+        let state = state().await;
+        assert_eq!(targets(&state, vec![1, 2], false).await, ALL);
+        let progress = run(&state, WarmTrigger::Admin).await;
+        assert_eq!((progress.total, progress.done, progress.failed), (4, 4, 0));
+        for key in ALL.map(|target| target.key(&state)) {
+            assert!(state.cache.get(&key).await.unwrap().is_some(), "{key}");
+        }
+        // everything is recomputed, cached or not
+        assert_eq!(targets(&state, vec![1, 2], false).await, ALL);
+    }
+
+    #[tokio::test]
+    async fn refills_only_warm_what_is_missing() {
+        let state = state().await;
+        run(&state, WarmTrigger::Startup).await;
+        assert!(targets(&state, vec![1, 2], true).await.is_empty());
+
+        state
+            .cache
+            .invalidate(&[CacheTag::Category(2)])
+            .await
+            .unwrap();
+        assert_eq!(
+            targets(&state, vec![1, 2], true).await,
+            [Target::Category(2)]
+        );
+        let progress = run(&state, WarmTrigger::Bulk).await;
+        assert_eq!((progress.total, progress.done), (2, 2));
+        assert!(targets(&state, vec![1, 2], true).await.is_empty());
+
+        state.cache.purge().await.unwrap();
+        assert_eq!(targets(&state, vec![1, 2], true).await, ALL);
+        let progress = run(&state, WarmTrigger::Purge).await;
+        assert_eq!((progress.total, progress.done), (4, 4));
+    }
+}
@@ -1,6 +1,7 @@
 import React, { createContext, useContext, useState } from 'react';
 import type { ReactNode } from 'react';
 import type { CartItem, Product } from '../types';
+import { getProductsByIds } from '../services/api';
 
 interface CartContextType {
   cart: CartItem[];
@@ -10,6 +11,7 @@ interface CartContextType {
   clearCart: () => void;
   getCartTotal: () => number;
   getCartCount: () => number;
+  refreshCart: () => Promise<void>;
 }
 
 const CartContext = createContext<CartContextType | undefined>(undefined);
@@ -77,6 +79,26 @@ export const CartProvider: React.FC<CartProviderProps> = ({ children }) => {
     return cart.reduce((count, item) => count + item.quantity, 0);
   };
 
+  // Reloads the products of the cart in one request, picking up price changes
+  // and dropping products that no longer exist
+  const refreshCart = async () => {
+    if (cart.length === 0) {
+      return;
+    }
+    const { products } = await getProductsByIds(cart.map((item) => item.product.product_id));
+    const latest = new Map(products.map((product) => [product.product_id, product]));
+    setCart((prevCart) =>
+      prevCart.flatMap((item) => {
+        const product = latest.get(item.product.product_id);
+        if (product) {
+          return [{ ...item, product }];
+        }
+        // items added while the request ran are kept as they are
+        return cart.some((old) => old.product.product_id === item.product.product_id) ? [] : [item];
+      })
+    );
+  };
+
   const value: CartContextType = {
     cart,
     addToCart,
@@ -85,6 +107,7 @@ export const CartProvider: React.FC<CartProviderProps> = ({ children }) => {
     clearCart,
     getCartTotal,
     getCartCount,
+    refreshCart,
   };
 
   return <CartContext.Provider value={value}>{children}</CartContext.Provider>;
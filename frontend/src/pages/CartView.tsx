@@ -1,4 +1,4 @@
-import React from 'react';
+import React, { useEffect } from 'react';
 import { useNavigate } from 'react-router-dom';
 import { Box, Container, Typography, Button, Divider } from '@mui/material';
 import ShoppingCartIcon from '@mui/icons-material/ShoppingCart';
@@ -7,7 +7,14 @@ import { useCart } from '../context/CartContext';
 
 const CartView: React.FC = () => {
   const navigate = useNavigate();
-  const { cart, updateQuantity, removeFromCart, clearCart, getCartTotal } = useCart();
+  const { cart, updateQuantity, removeFromCart, clearCart, getCartTotal, refreshCart } = useCart();
+
+  // Picks up price changes and removed products when the cart is opened
+  useEffect(() => {
+    refreshCart().catch((err) => {
+      console.error('Error refreshing cart:', err);
+    });
+  }, []);
 
   if (cart.length === 0) {
     return (
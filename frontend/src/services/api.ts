@@ -7,6 +7,8 @@ import type {
   AdminLoginResponse,
   BackendProduct,
   BackendCategory,
+  BackendCategoryResponse,
+  BackendProductBatch,
 } from '../types';
 import {
   transformProduct,
@@ -23,11 +25,14 @@ const USE_MOCK_DATA = import.meta.env.VITE_USE_MOCK_DATA === 'true';
 
 // Configure base URL for API (update this when backend is ready)
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
+const API_VERSION = 'v1';
+// Longer id lists are POSTed to /products to stay clear of URL length limits
+const MAX_QUERY_IDS_LENGTH = 1500;
 
 console.log(`[API] Using ${USE_MOCK_DATA ? 'MOCK' : 'REAL'} data`);
 
 const api = axios.create({
-  baseURL: API_BASE_URL,
+  baseURL: `${API_BASE_URL}/api/${API_VERSION}`,
   headers: {
     'Content-Type': 'application/json',
   },
@@ -82,6 +87,32 @@ export const getProduct = async (id: number): Promise<Product> => {
   return transformProduct(response.data);
 };
 
+/**
+ * Get products by ID in one request, e.g. to refresh a cart
+ * GET /products?ids=1,2,3 -> 200 { products: product[], missing: id[] }
+ * POST /products { ids } for lists too long for a query string
+ */
+export const getProductsByIds = async (
+  ids: number[]
+): Promise<{ products: Product[]; missing: number[] }> => {
+  if (USE_MOCK_DATA) {
+    const results = await Promise.all(ids.map((id) => mockApi.getProduct(id).catch(() => null)));
+    return {
+      products: results.filter((product): product is Product => product !== null),
+      missing: ids.filter((_, index) => results[index] === null),
+    };
+  }
+  const query = ids.join(',');
+  const response =
+    query.length <= MAX_QUERY_IDS_LENGTH
+      ? await api.get<BackendProductBatch>('/products', { params: { ids: query } })
+      : await api.post<BackendProductBatch>('/products', { ids });
+  return {
+    products: transformProducts(response.data.products),
+    missing: response.data.missing,
+  };
+};
+
 /**
  * Get all parent categories
  * GET /categories -> 200 { parent_categories[] }
@@ -102,11 +133,7 @@ export const getCategory = async (id: number): Promise<CategoryResponse> => {
   if (USE_MOCK_DATA) {
     return mockApi.getCategory(id);
   }
-  const response = await api.get<{
-    products: BackendProduct[];
-    sub_categories: BackendCategory[];
-    parent_categories: BackendCategory[];
-  }>(`/category/${id}`);
+  const response = await api.get<BackendCategoryResponse>(`/category/${id}`);
 
   return {
     products: transformProducts(response.data.products),
@@ -0,0 +1,103 @@
+// Generated from the backend models by `cargo run -- types`, do not edit.
+// `cargo run -- types --check` and `cargo test` fail when this file is out of date.
+
+export interface Category {
+  id: number;
+  name: string;
+  description: string | null;
+  parent_id: number | null;
+}
+
+export interface DeleteCategory {
+  category_id: number;
+}
+
+export interface DeleteProduct {
+  product_id: number;
+}
+
+export interface DeleteStopWord {
+  word: string;
+}
+
+export interface DeleteSynonymGroup {
+  group_id: number;
+}
+
+export interface GetCategoryResponse {
+  products: Product[];
+  sub_categories: Category[];
+  parent_categories: Category[];
+}
+
+export interface LoginPayload {
+  username: string;
+  password: string;
+}
+
+export interface NewCategory {
+  name: string;
+  description: string | null;
+  parent_id: number | null;
+}
+
+export interface NewProduct {
+  name: string;
+  description: string | null;
+  price: number;
+  category_id: number | null;
+}
+
+export interface NewStopWord {
+  word: string;
+}
+
+export interface NewSynonymGroup {
+  terms: string[];
+}
+
+export interface Product {
+  id: number;
+  name: string;
+  description: string | null;
+  price: number;
+  category_id: number | null;
+}
+
+export interface ProductBatch {
+  products: Product[];
+  missing: number[];
+}
+
+export interface ProductIds {
+  ids: number[];
+}
+
+export interface ProductPage {
+  items: Product[];
+  page: number;
+  page_size: number;
+  next_page: number | null;
+}
+
+export interface PurgeResponse {
+  deleted_keys: number;
+  warming: boolean;
+}
+
+export interface SynonymGroup {
+  id: number;
+  terms: string[];
+}
+
+export interface WarmProgress {
+  running: boolean;
+  trigger: WarmTrigger | null;
+  total: number;
+  done: number;
+  failed: number;
+  started_at: number | null;
+  finished_at: number | null;
+}
+
+export type WarmTrigger = "startup" | "schedule" | "admin" | "purge" | "bulk";
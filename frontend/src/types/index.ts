@@ -17,22 +17,14 @@ export interface Category {
   parent_category_id: number | null;
 }
 
-// Backend types (match the Rust backend structure)
-
-export interface BackendProduct {
-  id: number;
-  name: string;
-  description: string | null;
-  price: number;
-  category_id: number | null;
-}
-
-export interface BackendCategory {
-  id: number;
-  name: string;
-  description: string | null;
-  parent_id: number | null;
-}
+// Backend types, generated from the Rust models into ./backend.ts (`npm run types`)
+
+export type {
+  Product as BackendProduct,
+  Category as BackendCategory,
+  GetCategoryResponse as BackendCategoryResponse,
+  ProductBatch as BackendProductBatch,
+} from './backend';
 
 export interface CartItem {
   product: Product;